@@ -0,0 +1,334 @@
+use proc_macro::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{parse_macro_input, Data, DeriveInput, LitStr, Type};
+
+/// Implement `aria2_rs_yet::call::Call` for a struct.
+///
+/// ```ignore
+/// #[derive(Aria2Call)]
+/// #[aria2(method = "aria2.getUris", response = Vec<TellStatusReplyUri>)]
+/// pub struct GetUris {
+///     pub gid: Gid,
+/// }
+/// ```
+///
+/// Container attributes:
+/// - `method = "..."`, required, the rpc method name.
+/// - `response = Type`, required, the type the result is decoded into.
+/// - `no_token`, do not put the secret token first, as for the `system.` methods,
+///   sets `Call::needs_token`.
+/// - `idempotent`, the call can be sent again, sets `Call::IDEMPOTENT`.
+/// - `bulk`, the call is made in large numbers, sets `Call::PRIORITY` to `Priority::Bulk`.
+///
+/// Fields are serialized as positional params in declaration order. A `None`
+/// field is left out when no later param is serialized, and is otherwise
+/// serialized as the `Default` of its type, such as `{}` for options, to keep
+/// the later params in place. Fields marked `#[aria2(skip)]` are not serialized. The field marked
+/// `#[aria2(gid)]`, of type `Gid`, is returned by `Call::gid`.
+#[proc_macro_derive(Aria2Call, attributes(aria2))]
+pub fn derive_aria2_call(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `aria2_rs_yet::projection::Projection` and `Deserialize` for a
+/// struct with named fields, each named after a marker in
+/// `aria2_rs_yet::projection::keys`.
+#[proc_macro_derive(Projection)]
+pub fn derive_projection(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_projection(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_projection(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Projection can only be derived for structs with named fields",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "Projection can not be derived for generic structs",
+        ));
+    }
+
+    let krate = quote!(::aria2_rs_yet);
+    let keys = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        quote_spanned!(ident.span()=> #krate::projection::keys::#ident)
+    });
+    let keys: Vec<_> = keys.collect();
+    let idents = fields.iter().map(|field| field.ident.as_ref().unwrap());
+    let name = &input.ident;
+
+    Ok(quote! {
+        impl #krate::projection::Projection for #name {
+            const KEYS: &'static [#krate::call::TellStatusField] = &[
+                #(<#keys as #krate::projection::Key>::FIELD,)*
+            ];
+        }
+
+        impl<'de> #krate::__private::serde::Deserialize<'de> for #name {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: #krate::__private::serde::Deserializer<'de>,
+            {
+                let mut map = <#krate::__private::serde_json::Map<
+                    ::std::string::String,
+                    #krate::__private::serde_json::Value,
+                > as #krate::__private::serde::Deserialize>::deserialize(deserializer)?;
+                Ok(Self {
+                    #(#idents: #krate::projection::take::<#keys, _, D::Error>(&mut map)?,)*
+                })
+            }
+        }
+    })
+}
+
+struct Container {
+    method: LitStr,
+    response: Type,
+    no_token: bool,
+    idempotent: bool,
+    bulk: bool,
+}
+
+fn parse_container(input: &DeriveInput) -> syn::Result<Container> {
+    let mut method = None;
+    let mut response = None;
+    let mut no_token = false;
+    let mut idempotent = false;
+    let mut bulk = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("aria2")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("method") {
+                method = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("response") {
+                response = Some(meta.value()?.parse::<Type>()?);
+            } else if meta.path.is_ident("no_token") {
+                no_token = true;
+            } else if meta.path.is_ident("idempotent") {
+                idempotent = true;
+            } else if meta.path.is_ident("bulk") {
+                bulk = true;
+            } else {
+                return Err(meta.error("unknown aria2 attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    let method = method.ok_or_else(|| {
+        syn::Error::new_spanned(&input.ident, "missing #[aria2(method = \"...\")]")
+    })?;
+    let response = response.ok_or_else(|| {
+        syn::Error::new_spanned(&input.ident, "missing #[aria2(response = Type)]")
+    })?;
+    Ok(Container {
+        method,
+        response,
+        no_token,
+        idempotent,
+        bulk,
+    })
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    gid: bool,
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("aria2")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                attrs.skip = true;
+            } else if meta.path.is_ident("gid") {
+                attrs.gid = true;
+            } else {
+                return Err(meta.error("unknown aria2 attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(attrs)
+}
+
+/// `T` of an `Option<T>` type.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let Container {
+        method,
+        response,
+        no_token,
+        idempotent,
+        bulk,
+    } = parse_container(&input)?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Aria2Call can only be derived for structs",
+            ))
+        }
+    };
+
+    // serialized fields with the inner type of the `Option` ones
+    let mut serialized = Vec::new();
+    let mut gid = None;
+    for (index, field) in fields.iter().enumerate() {
+        let attrs = parse_field(field)?;
+        let member = match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = syn::Index::from(index);
+                quote!(#index)
+            }
+        };
+        if attrs.gid {
+            if gid.is_some() {
+                return Err(syn::Error::new_spanned(field, "duplicate #[aria2(gid)]"));
+            }
+            gid = Some(member.clone());
+        }
+        if attrs.skip {
+            continue;
+        }
+        serialized.push((member, option_inner(&field.ty)));
+    }
+
+    let params: Vec<_> = serialized
+        .iter()
+        .enumerate()
+        .map(|(index, (member, inner))| {
+            let Some(inner) = inner else {
+                return quote! {
+                    serializer.serialize_element(&self.#member)?;
+                };
+            };
+            // a `None` needs a placeholder when a later param is serialized
+            let later = &serialized[index + 1..];
+            let placeholder = if later.iter().any(|(_, inner)| inner.is_none()) {
+                quote!(else)
+            } else if later.is_empty() {
+                return quote! {
+                    if let Some(ref value) = self.#member {
+                        serializer.serialize_element(value)?;
+                    }
+                };
+            } else {
+                let later = later.iter().map(|(member, _)| quote!(self.#member.is_some()));
+                quote!(else if #(#later)||*)
+            };
+            quote! {
+                if let Some(ref value) = self.#member {
+                    serializer.serialize_element(value)?;
+                } #placeholder {
+                    serializer.serialize_element(&<#inner as ::std::default::Default>::default())?;
+                }
+            }
+        })
+        .collect();
+
+    let krate = quote!(::aria2_rs_yet);
+    let token = match (no_token, params.is_empty()) {
+        (false, _) => quote!(),
+        (true, true) => quote! {
+            fn needs_token(&self) -> bool {
+                false
+            }
+
+            fn to_params(self, _: Option<&str>) -> Option<#krate::call::Aria2Params<'_, Self>>
+            where
+                Self: Sized,
+            {
+                None
+            }
+        },
+        (true, false) => quote! {
+            fn needs_token(&self) -> bool {
+                false
+            }
+        },
+    };
+
+    let gid = match gid {
+        Some(member) => quote! {
+            fn gid(&self) -> ::std::option::Option<&#krate::Gid> {
+                ::std::option::Option::Some(&self.#member)
+            }
+        },
+        None => quote!(),
+    };
+
+    let priority = if bulk {
+        quote!(#krate::limit::Priority::Bulk)
+    } else {
+        quote!(#krate::limit::Priority::Interactive)
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let serialize_params = if params.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            fn serialize_params<S: #krate::__private::serde::ser::SerializeSeq>(
+                &self,
+                serializer: &mut S,
+            ) -> ::std::result::Result<(), S::Error> {
+                #(#params)*
+                Ok(())
+            }
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics #krate::call::Call for #name #ty_generics #where_clause {
+            type Response = #response;
+            const IDEMPOTENT: bool = #idempotent;
+            const PRIORITY: #krate::limit::Priority = #priority;
+
+            fn method(&self) -> ::std::borrow::Cow<'static, str> {
+                ::std::borrow::Cow::Borrowed(#method)
+            }
+
+            #gid
+
+            #serialize_params
+
+            #token
+        }
+    })
+}
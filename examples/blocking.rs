@@ -0,0 +1,28 @@
+#![allow(clippy::result_large_err)]
+
+use aria2_rs_yet::blocking::Client;
+use aria2_rs_yet::call::{GetVersion, TellActive};
+use aria2_rs_yet::{ConnectionMeta, Result};
+
+fn main() -> Result<()> {
+    tracing_subscriber::fmt().with_max_level(tracing::Level::INFO).init();
+
+    let (client, notifications) = Client::connect(
+        ConnectionMeta::new(
+            "ws://localhost:6800/jsonrpc",
+            Some("<rpc-secret>"),
+        )
+    )?;
+
+    let version = client.call(GetVersion)?;
+    println!("{:?}", version);
+
+    let active = client.call(TellActive::new())?;
+    println!("{:?}", active);
+
+    println!("waiting for notifications");
+    for notification in notifications {
+        println!("{:?}", notification);
+    }
+    Ok(())
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::result_large_err)]
+
 use aria2_rs_yet::{Client, ConnectionMeta, Result};
 use aria2_rs_yet::call::{SystemListMethods, GetVersion, AddUri, TellStatus, TellStatusField, TellStopped};
 use aria2_rs_yet::options::Aria2Options;
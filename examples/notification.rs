@@ -1,3 +1,5 @@
+#![allow(clippy::result_large_err)]
+
 use tokio::signal;
 
 use aria2_rs_yet::{Client, ConnectionMeta, Result};
@@ -0,0 +1,37 @@
+#![allow(clippy::result_large_err)]
+
+use std::time::Duration;
+
+use aria2_rs_yet::call::{AddUri, TellStatus, TellStatusField};
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::{Client, Result};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt().with_max_level(tracing::Level::INFO).init();
+
+    let server = FakeAria2::builder()
+        .secret("<rpc-secret>")
+        .file_size(4 * 1024 * 1024)
+        .start()
+        .await
+        .expect("start fake aria2");
+
+    let (client, mut rx) = Client::connect(server.meta()).await?;
+
+    let gid = client.call(AddUri::uris(vec!["http://example.com/a.zip"])).await?;
+    println!("{:?}", rx.recv().await);
+
+    // the link drops while the download completes
+    server.drop_connections();
+    server.advance(Duration::from_secs(10));
+
+    // reported again once the client reconnected
+    println!("{:?}", rx.recv().await);
+
+    let status = client.call(
+        TellStatus::new(gid).fields(Some([TellStatusField::Status, TellStatusField::CompletedLength]))
+    ).await?;
+    println!("{:?}", status);
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use std::future::Future;
+
+use crate::call::{Call, RawCall};
+use crate::ws::ClientInner;
+use crate::{Client, Result};
+
+/// What services built on top of this crate need from a client, implemented
+/// by [`Client`] and, with the `mock` feature, by
+/// [`MockClient`](crate::mock::MockClient) for unit tests.
+///
+/// `rotate_secret` and `close` of [`Client`] are left out, they manage the
+/// connection rather than talk to aria2.
+pub trait Aria2Api: Send + Sync {
+    fn call<C>(&self, call: C) -> impl Future<Output = Result<C::Response>> + Send
+    where
+        C: Call + Send + 'static;
+
+    /// call a method without a dedicated `Call`, see [`RawCall`].
+    fn call_raw(
+        &self,
+        method: impl Into<String>,
+        params: Vec<serde_json::Value>,
+    ) -> impl Future<Output = Result<serde_json::Value>> + Send {
+        self.call(RawCall::new(method, params))
+    }
+}
+
+impl Aria2Api for Client {
+    fn call<C>(&self, call: C) -> impl Future<Output = Result<C::Response>> + Send
+    where
+        C: Call + Send + 'static,
+    {
+        ClientInner::call(self, call)
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::call::TellStatusReplyFile;
+
+/// Pieces a download has, decoded from the hex `bitfield` aria2 reports for a
+/// download or a peer.
+///
+/// The highest bit of the first byte represents the piece of index 0, spare
+/// bits in the last byte are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitfield {
+    bytes: Vec<u8>,
+    num_pieces: u64,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseBitfieldError {
+    #[error("invalid hex character {0:?} in bitfield")]
+    InvalidHex(char),
+    #[error("bitfield of {bytes} bytes can not hold {num_pieces} pieces")]
+    TooShort { bytes: usize, num_pieces: u64 },
+}
+
+impl Bitfield {
+    pub fn from_hex(hex: &str, num_pieces: u64) -> Result<Self, ParseBitfieldError> {
+        let digit = |c: u8| {
+            (c as char)
+                .to_digit(16)
+                .map(|d| d as u8)
+                .ok_or(ParseBitfieldError::InvalidHex(c as char))
+        };
+        let bytes = hex
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| match pair {
+                [hi, lo] => Ok(digit(*hi)? << 4 | digit(*lo)?),
+                [hi] => Ok(digit(*hi)? << 4),
+                _ => unreachable!(),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_bytes(bytes, num_pieces)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>, num_pieces: u64) -> Result<Self, ParseBitfieldError> {
+        if (bytes.len() as u64) * 8 < num_pieces {
+            return Err(ParseBitfieldError::TooShort {
+                bytes: bytes.len(),
+                num_pieces,
+            });
+        }
+        Ok(Self { bytes, num_pieces })
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn num_pieces(&self) -> u64 {
+        self.num_pieces
+    }
+
+    /// whether the piece of `index` is completed, false if out of range.
+    pub fn has(&self, index: u64) -> bool {
+        index < self.num_pieces && self.bytes[(index / 8) as usize] & (0x80 >> (index % 8)) != 0
+    }
+
+    pub fn completed_pieces(&self) -> u64 {
+        (0..self.num_pieces).filter(|&i| self.has(i)).count() as u64
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.completed_pieces() == self.num_pieces
+    }
+
+    /// contiguous ranges of completed pieces, in ascending order.
+    pub fn ranges(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        let mut index = 0;
+        std::iter::from_fn(move || {
+            while index < self.num_pieces && !self.has(index) {
+                index += 1;
+            }
+            if index == self.num_pieces {
+                return None;
+            }
+            let start = index;
+            while index < self.num_pieces && self.has(index) {
+                index += 1;
+            }
+            Some(start..index)
+        })
+    }
+
+    /// bytes covered by completed pieces, the last piece may be shorter than
+    /// `piece_length`. Pieces past `total_length` count for nothing.
+    pub fn completed_length(&self, piece_length: u64, total_length: u64) -> u64 {
+        self.ranges()
+            .map(|r| {
+                let end = r.end.saturating_mul(piece_length).min(total_length);
+                end.saturating_sub(r.start.saturating_mul(piece_length))
+            })
+            .sum()
+    }
+
+    /// completed bytes of each file, files are laid out back to back in the
+    /// order given, as in [`TellStatusReply::files`](crate::call::TellStatusReply::files).
+    pub fn file_completion(&self, piece_length: u64, files: &[TellStatusReplyFile]) -> Vec<u64> {
+        let mut offset = 0;
+        files
+            .iter()
+            .map(|file| {
+                let (start, end) = (offset, offset + file.length);
+                offset = end;
+                if file.length == 0 || piece_length == 0 {
+                    return 0;
+                }
+                (start / piece_length..end.div_ceil(piece_length))
+                    .filter(|&i| self.has(i))
+                    .map(|i| end.min((i + 1) * piece_length) - start.max(i * piece_length))
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// text bar of `width` cells, each cell shades the share of completed
+    /// pieces it covers from ' ' (none) to '█' (all).
+    pub fn bar(&self, width: usize) -> String {
+        const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+        let width = width as u64;
+        (0..width)
+            .map(|cell| {
+                let start = cell * self.num_pieces / width;
+                let end = ((cell + 1) * self.num_pieces / width).max(start + 1);
+                let end = end.min(self.num_pieces);
+                if start >= end {
+                    return SHADES[0];
+                }
+                let done = (start..end).filter(|&i| self.has(i)).count() as u64;
+                match done {
+                    0 => SHADES[0],
+                    d if d == end - start => SHADES[4],
+                    d => SHADES[1 + (d * 3 / (end - start)) as usize],
+                }
+            })
+            .collect()
+    }
+}
+
+/// one character per piece, '#' for completed and '.' for missing ones.
+impl fmt::Display for Bitfield {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..self.num_pieces {
+            f.write_str(if self.has(i) { "#" } else { "." })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(length: u64) -> TellStatusReplyFile {
+        serde_json::from_value(serde_json::json!({
+            "index": "1",
+            "length": length.to_string(),
+            "completedLength": "0",
+            "path": "",
+            "selected": "true",
+            "uris": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn from_hex() {
+        assert_eq!(Bitfield::from_hex("A5", 8).unwrap().as_bytes(), [0xa5]);
+        assert_eq!(Bitfield::from_hex("a", 4).unwrap().as_bytes(), [0xa0]);
+        assert_eq!(Bitfield::from_hex("ag", 8), Err(ParseBitfieldError::InvalidHex('g')));
+        assert_eq!(
+            Bitfield::from_hex("ff", 9),
+            Err(ParseBitfieldError::TooShort { bytes: 1, num_pieces: 9 })
+        );
+    }
+
+    #[test]
+    fn spare_bits_are_ignored() {
+        let bitfield = Bitfield::from_hex("ff", 5).unwrap();
+        assert_eq!(bitfield.completed_pieces(), 5);
+        assert!(bitfield.is_complete());
+        assert!(!bitfield.has(5));
+        assert!(bitfield.ranges().eq(std::iter::once(0..5)));
+        assert_eq!(bitfield.to_string(), "#####");
+    }
+
+    #[test]
+    fn ranges() {
+        let bitfield = Bitfield::from_hex("a5", 8).unwrap();
+        assert_eq!(bitfield.ranges().collect::<Vec<_>>(), [0..1, 2..3, 5..6, 7..8]);
+        let bitfield = Bitfield::from_hex("00", 8).unwrap();
+        assert_eq!(bitfield.ranges().count(), 0);
+        let bitfield = Bitfield::from_hex("", 0).unwrap();
+        assert_eq!(bitfield.ranges().count(), 0);
+        assert!(bitfield.is_complete());
+    }
+
+    #[test]
+    fn completed_length_with_short_last_piece() {
+        // 3 pieces of 10 bytes, the last one holding 5
+        let length = |hex| Bitfield::from_hex(hex, 3).unwrap().completed_length(10, 25);
+        assert_eq!(length("e0"), 25);
+        assert_eq!(length("20"), 5);
+        assert_eq!(length("c0"), 20);
+        assert_eq!(length("00"), 0);
+        // a total length short of the pieces, as reported mid-update
+        let bitfield = Bitfield::from_hex("e0", 3).unwrap();
+        assert_eq!(bitfield.completed_length(10, 15), 15);
+        assert_eq!(Bitfield::from_hex("20", 3).unwrap().completed_length(10, 15), 0);
+    }
+
+    #[test]
+    fn file_completion() {
+        // pieces 0 and 2 over bytes 0..10 and 20..25
+        let bitfield = Bitfield::from_hex("a0", 3).unwrap();
+        let files = [file(15), file(0), file(10)];
+        assert_eq!(bitfield.file_completion(10, &files), [10, 0, 5]);
+        assert_eq!(bitfield.file_completion(0, &files), [0, 0, 0]);
+    }
+
+    #[test]
+    fn bar() {
+        let bitfield = Bitfield::from_hex("c0", 4).unwrap();
+        assert_eq!(bitfield.bar(4), "██  ");
+        assert_eq!(bitfield.bar(2), "█ ");
+        assert_eq!(bitfield.bar(8), "████    ");
+        assert_eq!(Bitfield::from_hex("80", 4).unwrap().bar(1), "░");
+        assert_eq!(Bitfield::from_hex("e0", 4).unwrap().bar(1), "▓");
+        assert_eq!(Bitfield::from_hex("", 0).unwrap().bar(3), "   ");
+    }
+}
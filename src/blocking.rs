@@ -0,0 +1,126 @@
+//! A synchronous client, for programs without an async runtime.
+//!
+//! ```ignore
+//! use aria2_rs_yet::blocking::Client;
+//! use aria2_rs_yet::call::GetVersion;
+//!
+//! let (client, notifications) = Client::connect(meta)?;
+//! println!("{:?}", client.call(GetVersion)?);
+//! for notification in notifications {
+//!     println!("{notification:?}");
+//! }
+//! ```
+//!
+//! The client runs the async [`Client`](crate::Client) on a private runtime.
+//! Its methods block the calling thread, so they must not be used from
+//! within an async runtime. Dropping it does not block, and is fine anywhere.
+
+#![allow(clippy::result_large_err)]
+
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+use crate::call::Call;
+use crate::error::Error;
+use crate::{ClientBuilder, ConnectionMeta, Notification, Result, Secret};
+
+/// A blocking handle to aria2, cheap to clone.
+///
+/// The runtime and the connection stop once the client, its clones and its
+/// [`Notifications`] are all dropped.
+#[derive(Clone)]
+pub struct Client {
+    inner: crate::Client,
+    runtime: Arc<Background>,
+}
+
+/// The private runtime, shut down without waiting once the last handle is
+/// dropped, as a runtime can not be dropped from within another one.
+struct Background(Option<Runtime>);
+
+impl Background {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.0.as_ref().unwrap().block_on(future)
+    }
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl Client {
+    pub fn connect(meta: ConnectionMeta) -> Result<(Self, Notifications)> {
+        Self::connect_with(ClientBuilder::new(meta))
+    }
+
+    /// connect with the options set on `builder`.
+    pub fn connect_with(builder: ClientBuilder) -> Result<(Self, Notifications)> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("aria2-rs-yet")
+            .enable_all()
+            .build()
+            .map_err(Error::Io)?;
+        let (inner, rx) = runtime.block_on(builder.connect())?;
+        let runtime = Arc::new(Background(Some(runtime)));
+        let notifications = Notifications {
+            rx,
+            _runtime: runtime.clone(),
+        };
+        Ok((Self { inner, runtime }, notifications))
+    }
+
+    pub fn call<C: Call>(&self, call: C) -> Result<C::Response> {
+        self.runtime.block_on(self.inner.call(call))
+    }
+
+    /// call a method by name, see [`RawCall`](crate::call::RawCall).
+    pub fn call_raw(
+        &self,
+        method: impl Into<String>,
+        params: Vec<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        self.runtime.block_on(self.inner.call_raw(method, params))
+    }
+
+    /// switch to a new rpc secret without reconnecting, as the async client does.
+    pub fn rotate_secret(&self, secret: impl Into<Secret>, change_on_server: bool) -> Result<()> {
+        self.runtime
+            .block_on(self.inner.rotate_secret(secret, change_on_server))
+    }
+
+    /// drain the calls in flight and close the connection, as `close` of the
+    /// async [`Client`](crate::Client) does.
+    pub fn close(&self) -> Result<()> {
+        self.runtime.block_on(self.inner.close())
+    }
+}
+
+/// Notifications of a blocking [`Client`], which keep coming while this is
+/// kept, even once the client is dropped.
+pub struct Notifications {
+    rx: mpsc::UnboundedReceiver<Notification>,
+    _runtime: Arc<Background>,
+}
+
+impl Notifications {
+    /// a notification already received, without waiting.
+    pub fn try_next(&mut self) -> Option<Notification> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl Iterator for Notifications {
+    type Item = Notification;
+
+    fn next(&mut self) -> Option<Notification> {
+        self.rx.blocking_recv()
+    }
+}
@@ -1,19 +1,46 @@
+use std::borrow::Cow;
+
 use serde::ser::{SerializeSeq, Serializer};
 use serde_with::{serde_as, DisplayFromStr};
 
+pub use aria2_rs_yet_derive::Aria2Call;
+
+use crate::limit::Priority;
 use crate::options::Aria2Options;
+use crate::{Bitfield, DownloadErrorCode, Gid, ParseBitfieldError};
 
 pub trait Call {
     type Response: serde::de::DeserializeOwned;
 
-    fn method(&self) -> &'static str;
+    /// whether sending it again is harmless, when it is not known if aria2
+    /// got it, see [`RetryPolicy`](crate::retry::RetryPolicy)
+    const IDEMPOTENT: bool = false;
+    /// when to send it under a [`RateLimit`](crate::limit::RateLimit)
+    const PRIORITY: Priority = Priority::Interactive;
+
+    fn method(&self) -> Cow<'static, str>;
+    /// the download the call is about, if any, for tracing
+    fn gid(&self) -> Option<&Gid> {
+        None
+    }
+    /// whether the token is put first, false for the `system.` methods
+    fn needs_token(&self) -> bool {
+        true
+    }
     fn serialize_params<S: SerializeSeq>(&self, _serializer: &mut S) -> Result<(), S::Error> {
         Ok(())
     }
+    /// the params, with the current `token` first when [`needs_token`](Call::needs_token).
+    ///
+    /// Params returned without the token given are sent without one. The
+    /// client sends the leading token as of when the request goes out, so a
+    /// call retried after a rotation uses the new one. Without a secret set,
+    /// [`needs_token`](Call::needs_token) decides whether one set later is sent.
     fn to_params(self, token: Option<&str>) -> Option<Aria2Params<'_, Self>>
     where
         Self: Sized,
     {
+        let token = token.filter(|_| self.needs_token());
         Some(Aria2Params::new(token, self))
     }
 }
@@ -27,12 +54,20 @@ macro_rules! option_element {
 }
 
 /// https://aria2.github.io/manual/en/html/aria2c.html#rpc-authorization-secret-token
-#[derive(Debug)]
 pub struct Aria2Params<'a, T> {
     token: Option<&'a str>,
     params: T,
 }
 
+impl<T: std::fmt::Debug> std::fmt::Debug for Aria2Params<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Aria2Params")
+            .field("token", &self.token.map(|_| crate::secret::REDACTED_TOKEN))
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
 impl<'a, T> Aria2Params<'a, T> {
     /// token with prefix
     pub fn new(token: Option<&'a str>, params: T) -> Self {
@@ -40,6 +75,21 @@ impl<'a, T> Aria2Params<'a, T> {
     }
 }
 
+impl<T: Call> Aria2Params<'_, T> {
+    /// the leading token, if put first.
+    pub(crate) fn token(&self) -> Option<&str> {
+        self.token
+    }
+
+    /// the params after the leading token.
+    pub(crate) fn untokenized(self) -> serde_json::Result<Vec<serde_json::Value>> {
+        match serde_json::to_value(Aria2Params::new(None, self.params))? {
+            serde_json::Value::Array(params) => Ok(params),
+            _ => unreachable!("params serialize as a sequence"),
+        }
+    }
+}
+
 impl<T> serde::Serialize for Aria2Params<'_, T>
 where
     T: Call,
@@ -55,24 +105,50 @@ where
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "system.listMethods", response = Vec<String>, no_token, idempotent)]
 pub struct SystemListMethods;
-impl Call for SystemListMethods {
-    type Response = Vec<String>;
 
-    fn method(&self) -> &'static str {
-        "system.listMethods"
+/// https://aria2.github.io/manual/en/html/aria2c.html#methods
+///
+/// Any method with positional params, for those without a dedicated call.
+/// The token is put first unless the method is in the `system.` namespace.
+#[derive(Debug)]
+pub struct RawCall {
+    pub method: String,
+    pub params: Vec<serde_json::Value>,
+}
+
+impl RawCall {
+    pub fn new<M: Into<String>>(method: M, params: Vec<serde_json::Value>) -> Self {
+        Self {
+            method: method.into(),
+            params,
+        }
     }
+}
 
-    fn to_params(self, _: Option<&str>) -> Option<Aria2Params<'_, Self>>
-    where
-        Self: Sized,
-    {
-        None
+impl Call for RawCall {
+    type Response = serde_json::Value;
+
+    fn method(&self) -> Cow<'static, str> {
+        self.method.clone().into()
+    }
+
+    fn needs_token(&self) -> bool {
+        !self.method.starts_with("system.")
+    }
+
+    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        for param in &self.params {
+            serializer.serialize_element(param)?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.addUri", response = GidReply, bulk)]
 pub struct AddUri {
     pub uris: Vec<String>,
     pub options: Option<Aria2Options>,
@@ -107,39 +183,49 @@ impl AddUri {
     }
 }
 
-impl Call for AddUri {
-    type Response = GidReply;
+/// https://aria2.github.io/manual/en/html/aria2c.html#aria2.changeGlobalOption
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.changeGlobalOption", response = String)]
+pub struct ChangeGlobalOption {
+    pub options: Aria2Options,
+}
 
-    fn method(&self) -> &'static str {
-        "aria2.addUri"
-    }
-
-    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
-        serializer.serialize_element(&self.uris)?;
-        option_element!(self.options, serializer);
-        option_element!(self.position, serializer);
-        Ok(())
+impl ChangeGlobalOption {
+    pub fn new(options: Aria2Options) -> Self {
+        Self { options }
     }
 }
 
 #[derive(serde::Deserialize, Debug, Clone)]
 #[serde(transparent)]
-pub struct GidReply(pub String);
+pub struct GidReply(pub Gid);
 
-impl From<GidReply> for String {
+impl From<GidReply> for Gid {
     fn from(gid: GidReply) -> Self {
         gid.0
     }
 }
 
-#[derive(Debug)]
+impl From<GidReply> for String {
+    fn from(gid: GidReply) -> Self {
+        gid.0.into()
+    }
+}
+
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.getVersion", response = VersionReply, idempotent)]
 pub struct GetVersion;
-impl Call for GetVersion {
-    type Response = VersionReply;
 
-    fn method(&self) -> &'static str {
-        "aria2.getVersion"
-    }
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.getSessionInfo", response = SessionInfoReply, idempotent)]
+pub struct GetSessionInfo;
+
+/// https://aria2.github.io/manual/en/html/aria2c.html#aria2.getSessionInfo
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfoReply {
+    /// generated each time aria2 is invoked
+    pub session_id: String,
 }
 
 #[derive(serde::Deserialize, Debug, Clone)]
@@ -149,14 +235,16 @@ pub struct VersionReply {
     pub enabled_features: Vec<String>,
 }
 
-#[derive(Debug, serde::Serialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, serde::Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[serde(rename_all = "camelCase")]
 pub enum TellStatusField {
     Gid,
     Status,
     TotalLength,
     CompletedLength,
+    #[serde(rename = "uploadLength")]
     UploadedLength,
+    #[serde(rename = "bitfield")]
     BitField,
     DownloadSpeed,
     UploadSpeed,
@@ -176,6 +264,7 @@ pub enum TellStatusField {
     // bt nested fields not supported now
     // Bittorrent,
     VerifiedLength,
+    #[serde(rename = "verifyIntegrityPending")]
     VeriyIntegrityPending,
 }
 
@@ -188,8 +277,8 @@ impl TryFrom<&str> for TellStatusField {
             "status" => Ok(Self::Status),
             "totalLength" => Ok(Self::TotalLength),
             "completedLength" => Ok(Self::CompletedLength),
-            "uploadedLength" => Ok(Self::UploadedLength),
-            "bitField" => Ok(Self::BitField),
+            "uploadLength" | "uploadedLength" => Ok(Self::UploadedLength),
+            "bitfield" | "bitField" => Ok(Self::BitField),
             "downloadSpeed" => Ok(Self::DownloadSpeed),
             "uploadSpeed" => Ok(Self::UploadSpeed),
             "infoHash" => Ok(Self::InfoHash),
@@ -207,7 +296,7 @@ impl TryFrom<&str> for TellStatusField {
             "files" => Ok(Self::Files),
             // Bittorrent
             "verifiedLength" => Ok(Self::VerifiedLength),
-            "veriyIntegrityPending" => Ok(Self::VeriyIntegrityPending),
+            "verifyIntegrityPending" | "veriyIntegrityPending" => Ok(Self::VeriyIntegrityPending),
             _ => Err("Invalid TellStatusField"),
         }
     }
@@ -227,10 +316,33 @@ pub enum TaskStatus {
 /// https://aria2.github.io/manual/en/html/aria2c.html#aria2.tellStatus
 #[derive(Debug)]
 pub struct TellStatus {
-    pub gid: String,
+    pub gid: Gid,
     pub keys: Option<std::collections::HashSet<TellStatusField>>,
 }
 
+impl Call for TellStatus {
+    type Response = TellStatusReply;
+    const IDEMPOTENT: bool = true;
+
+    fn method(&self) -> Cow<'static, str> {
+        Cow::Borrowed("aria2.tellStatus")
+    }
+
+    fn gid(&self) -> Option<&Gid> {
+        Some(&self.gid)
+    }
+
+    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        serializer.serialize_element(&self.gid)?;
+        if let Some(ref keys) = self.keys {
+            // sorted, so that a recorded call matches on replay
+            let keys: std::collections::BTreeSet<_> = keys.iter().collect();
+            serializer.serialize_element(&keys)?;
+        }
+        Ok(())
+    }
+}
+
 macro_rules! tell_star {
     ($name: ident) => {
         impl $name {
@@ -243,15 +355,10 @@ macro_rules! tell_star {
                 self.keys = None;
 
                 if let Some(keys) = keys {
-                    let mut temp = std::collections::HashSet::new();
-                    for field in keys.into_iter() {
-                        match field.try_into() {
-                            Ok(f) => {
-                                temp.insert(f);
-                            }
-                            Err(e) => return Err(e),
-                        }
-                    }
+                    let temp = keys
+                        .into_iter()
+                        .map(TryInto::try_into)
+                        .collect::<Result<_, _>>()?;
                     self.keys = Some(temp);
                 }
                 Ok(self)
@@ -270,13 +377,9 @@ macro_rules! tell_star {
             where
                 F: Into<TellStatusField>,
             {
-                if let Some(ref mut keys) = self.keys {
-                    keys.insert(field.into());
-                } else {
-                    let mut keys = std::collections::HashSet::new();
-                    keys.insert(field.into());
-                    self.keys = Some(keys);
-                }
+                self.keys
+                    .get_or_insert_with(Default::default)
+                    .insert(field.into());
                 self
             }
 
@@ -285,14 +388,7 @@ macro_rules! tell_star {
                 I: IntoIterator<Item = F>,
                 F: Into<TellStatusField>,
             {
-                self.keys = None;
-                if let Some(fields) = fields {
-                    let mut keys = std::collections::HashSet::new();
-                    for field in fields.into_iter() {
-                        keys.insert(field.into());
-                    }
-                    self.keys = Some(keys);
-                }
+                self.keys = fields.map(|fields| fields.into_iter().map(Into::into).collect());
                 self
             }
         }
@@ -301,7 +397,7 @@ macro_rules! tell_star {
 
 impl TellStatus {
     /// create a new TellStatus
-    pub fn new<G: Into<String>>(gid: G) -> Self {
+    pub fn new<G: Into<Gid>>(gid: G) -> Self {
         Self {
             gid: gid.into(),
             keys: None,
@@ -310,7 +406,7 @@ impl TellStatus {
 
     pub fn new_with_fields<G, I, F>(gid: G, fields: I) -> Self
     where
-        G: Into<String>,
+        G: Into<Gid>,
         I: IntoIterator<Item = F>,
         F: Into<TellStatusField>,
     {
@@ -324,7 +420,7 @@ tell_star!(TellStatus);
 #[derive(Debug, Clone, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TellStatusReply {
-    pub gid: Option<String>,
+    pub gid: Option<Gid>,
     pub status: Option<TaskStatus>,
     #[serde_as(as = "Option<DisplayFromStr>")]
     pub total_length: Option<u64>,
@@ -343,10 +439,21 @@ pub struct TellStatusReply {
     pub num_pieces: Option<u64>,
     #[serde_as(as = "Option<DisplayFromStr>")]
     pub connections: Option<u64>,
+    pub error_code: Option<DownloadErrorCode>,
+    pub error_message: Option<String>,
     pub dir: Option<String>,
     pub files: Option<Vec<TellStatusReplyFile>>,
 }
 
+impl TellStatusReply {
+    /// decode `bitfield`, None unless both `bitfield` and `num_pieces` were
+    /// requested and the download has started.
+    pub fn pieces(&self) -> Option<Result<Bitfield, ParseBitfieldError>> {
+        let bitfield = self.bitfield.as_ref()?;
+        Some(Bitfield::from_hex(bitfield, self.num_pieces?))
+    }
+}
+
 #[serde_as]
 #[derive(Debug, Clone, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -375,23 +482,10 @@ pub struct TellStatusReplyUri {
     pub uri: String,
 }
 
-impl Call for TellStatus {
-    type Response = TellStatusReply;
-
-    fn method(&self) -> &'static str {
-        "aria2.tellStatus"
-    }
-
-    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
-        serializer.serialize_element(&self.gid)?;
-        option_element!(self.keys, serializer);
-        Ok(())
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.tellActive", response = Vec<TellStatusReply>, idempotent)]
 pub struct TellActive {
-    keys: Option<std::collections::HashSet<TellStatusField>>,
+    keys: Option<std::collections::BTreeSet<TellStatusField>>,
 }
 
 impl TellActive {
@@ -408,23 +502,16 @@ impl TellActive {
     }
 }
 
-tell_star!(TellActive);
-
-
-impl Call for TellActive {
-    type Response = Vec<TellStatusReply>;
-
-    fn method(&self) -> &'static str {
-        "aria2.tellActive"
-    }
-
-    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
-        option_element!(self.keys, serializer);
-        Ok(())
+impl Default for TellActive {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-#[derive(Debug)]
+tell_star!(TellActive);
+
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.tellWaiting", response = Vec<TellStatusReply>, idempotent)]
 pub struct TellWaiting {
     ///If offset is a positive integer, this method returns downloads in the range of [offset, offset + num).
     /// 
@@ -432,7 +519,7 @@ pub struct TellWaiting {
     /// Downloads in the response are in reversed order then.
     pub offset: i32,
     pub num: i32,
-    keys: Option<std::collections::HashSet<TellStatusField>>,
+    keys: Option<std::collections::BTreeSet<TellStatusField>>,
 }
 impl TellWaiting {
     pub fn new(offset: i32, num: i32) -> Self {
@@ -454,27 +541,12 @@ impl TellWaiting {
 
 tell_star!(TellWaiting);
 
-
-impl Call for TellWaiting {
-    type Response = Vec<TellStatusReply>;
-
-    fn method(&self) -> &'static str {
-        "aria2.tellWaiting"
-    }
-
-    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
-        serializer.serialize_element(&self.offset)?;
-        serializer.serialize_element(&self.num)?;
-        option_element!(self.keys, serializer);
-        Ok(())
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.tellStopped", response = Vec<TellStatusReply>, idempotent)]
 pub struct TellStopped {
     pub offset: i32,
     pub num: i32,
-    keys: Option<std::collections::HashSet<TellStatusField>>,
+    keys: Option<std::collections::BTreeSet<TellStatusField>>,
 }
 
 impl TellStopped {
@@ -496,63 +568,72 @@ impl TellStopped {
 }
 tell_star!(TellStopped);
 
-
-impl Call for TellStopped {
-    type Response = Vec<TellStatusReply>;
-
-    fn method(&self) -> &'static str {
-        "aria2.tellStopped"
-    }
-
-    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
-        serializer.serialize_element(&self.offset)?;
-        serializer.serialize_element(&self.num)?;
-        option_element!(self.keys, serializer);
-        Ok(())
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.getUris", response = Vec<TellStatusReplyUri>, idempotent)]
 pub struct GetUris {
-    pub gid: String,
+    #[aria2(gid)]
+    pub gid: Gid,
 }
 
 impl GetUris {
-    pub fn new<G: Into<String>>(gid: G) -> Self {
+    pub fn new<G: Into<Gid>>(gid: G) -> Self {
         Self { gid: gid.into() }
     }
 }
-impl Call for GetUris {
-    type Response = Vec<TellStatusReplyUri>;
-
-    fn method(&self) -> &'static str {
-        "aria2.getUris"
-    }
 
-    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
-        serializer.serialize_element(&self.gid)?;
-        Ok(())
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.getFiles", response = Vec<TellStatusReplyFile>, idempotent)]
+pub struct GetFiles {
+    #[aria2(gid)]
+    pub gid: Gid,
+}
+impl GetFiles {
+    pub fn new<G: Into<Gid>>(gid: G) -> Self {
+        Self { gid: gid.into() }
     }
 }
 
-#[derive(Debug)]
-pub struct GetFiles {
-    pub gid: String,
+/// https://aria2.github.io/manual/en/html/aria2c.html#aria2.getOption
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.getOption", response = Aria2Options, idempotent)]
+pub struct GetOption {
+    #[aria2(gid)]
+    pub gid: Gid,
 }
-impl GetFiles {
-    pub fn new<G: Into<String>>(gid: G) -> Self {
+
+impl GetOption {
+    pub fn new<G: Into<Gid>>(gid: G) -> Self {
         Self { gid: gid.into() }
     }
 }
-impl Call for GetFiles {
-    type Response = Vec<TellStatusReplyFile>;
 
-    fn method(&self) -> &'static str {
-        "aria2.getFiles"
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tell_status_keys_are_sorted() {
+        let call = TellStatus::new("2089b05ecca3d829")
+            .field(TellStatusField::Status)
+            .field(TellStatusField::Gid)
+            .field(TellStatusField::CompletedLength);
+        let params = serde_json::to_value(call.to_params(None)).unwrap();
+        assert_eq!(
+            params,
+            serde_json::json!(["2089b05ecca3d829", ["gid", "status", "completedLength"]])
+        );
     }
 
-    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
-        serializer.serialize_element(&self.gid)?;
-        Ok(())
+    #[test]
+    fn raw_call_token() {
+        let call = RawCall::new("aria2.getVersion", vec![]);
+        assert!(call.needs_token());
+        let params = serde_json::to_value(call.to_params(Some("token:t"))).unwrap();
+        assert_eq!(params, serde_json::json!(["token:t"]));
+
+        let call = RawCall::new("system.listMethods", vec![]);
+        assert!(!call.needs_token());
+        let params = serde_json::to_value(call.to_params(Some("token:t"))).unwrap();
+        assert_eq!(params, serde_json::json!([]));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,136 @@
+//! Record of the frames exchanged with aria2, one JSON object per line.
+//!
+//! ```text
+//! {"direction":"sent","frame":{"jsonrpc":"2.0","method":"aria2.getVersion","id":2,"params":["token:<redacted>"]}}
+//! {"direction":"received","frame":{"id":2,"jsonrpc":"2.0","result":{"enabledFeatures":[],"version":"1.37.0"}}}
+//! ```
+//!
+//! Written by a client built with [`ClientBuilder::record`](crate::ClientBuilder::record),
+//! served back by `testkit::ReplayServer`.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use serde_json::Value;
+
+pub use crate::secret::REDACTED_TOKEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    pub direction: Direction,
+    pub frame: Value,
+}
+
+/// Appends every frame to a cassette file, with the secret token and secret
+/// options redacted.
+///
+/// The file is written by a thread of its own, so recording does not block
+/// the connection. Dropping the recorder waits for the frames to be written.
+pub struct Recorder {
+    tx: Option<mpsc::Sender<Entry>>,
+    writer: Option<thread::JoinHandle<()>>,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let out = BufWriter::new(File::create(path)?);
+        let (tx, rx) = mpsc::channel();
+        let writer = thread::Builder::new()
+            .name("aria2-rs-yet-cassette".into())
+            .spawn(move || write_entries(out, rx))?;
+        Ok(Self {
+            tx: Some(tx),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn record(&mut self, direction: Direction, text: &str) {
+        let mut frame = match serde_json::from_str(text) {
+            Ok(frame) => frame,
+            Err(_) => Value::String(text.to_string()),
+        };
+        redact(&mut frame);
+        if let Some(tx) = &self.tx {
+            // the writer only stops once the sender is dropped
+            let _ = tx.send(Entry { direction, frame });
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// write the entries received, flushing whenever none is waiting.
+fn write_entries(mut out: BufWriter<File>, rx: mpsc::Receiver<Entry>) {
+    let write = |out: &mut BufWriter<File>, entry: &Entry| {
+        serde_json::to_writer(&mut *out, entry)
+            .map_err(io::Error::from)
+            .and_then(|_| out.write_all(b"\n"))
+    };
+    while let Ok(entry) = rx.recv() {
+        let mut next = Some(entry);
+        while let Some(entry) = next {
+            if let Err(e) = write(&mut out, &entry) {
+                tracing::error!("record frame error: {e}");
+            }
+            next = rx.try_recv().ok();
+        }
+        if let Err(e) = out.flush() {
+            tracing::error!("record frame error: {e}");
+        }
+    }
+}
+
+/// replace the `token:<secret>` param of a request frame, and the secrets
+/// among its options, such as the `rpc-secret` of a rotation.
+pub fn redact(frame: &mut Value) {
+    if let Some(params) = frame.get_mut("params") {
+        crate::secret::redact_params(params);
+    }
+}
+
+/// params of a request frame without the token and with secret options
+/// redacted, as matched on replay.
+pub fn untokenized_params(frame: &Value) -> Vec<Value> {
+    let mut params = match frame.get("params") {
+        Some(Value::Array(params)) => params.clone(),
+        _ => return vec![],
+    };
+    if params
+        .first()
+        .and_then(Value::as_str)
+        .is_some_and(|s| s.starts_with("token:"))
+    {
+        params.remove(0);
+    }
+    params.iter_mut().for_each(crate::secret::redact_options);
+    params
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line)?);
+    }
+    Ok(entries)
+}
@@ -0,0 +1,227 @@
+//! aria2's configuration file, `aria2.conf`, as given to `--conf-path`.
+//!
+//! ```text
+//! # comment
+//! enable-rpc=true
+//! rpc-listen-port=6800
+//! rpc-secret=<rpc-secret>
+//! header=X-A: 1
+//! header=X-B: 2
+//! ```
+//!
+//! One option per line, without the leading `--`. Lines starting with `#`
+//! are comments. An option given again replaces the earlier value, except
+//! the cumulative ones such as `header` which collect every value.
+
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+use crate::options::Aria2Options;
+use crate::ws::DEFAULT_PORT;
+use crate::ConnectionMeta;
+
+/// options aria2 collects when given several times, instead of keeping the last.
+const CUMULATIVE: &[&str] = &["header", "index-out"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfError {
+    #[error("line {0}: expect key=value")]
+    Syntax(usize),
+    #[error("invalid option value {0}")]
+    InvalidValue(serde_json::Error),
+    #[error("rpc is disabled by enable-rpc=false")]
+    RpcDisabled,
+}
+
+/// The options of a configuration file, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aria2Conf {
+    entries: Vec<(String, String)>,
+}
+
+impl Aria2Conf {
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        std::fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// every `key=value` line, repeated keys included.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// the value aria2 uses for `key`, the last one given.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// the options as [`Aria2Options`], those without a field in
+    /// [`extra`](Aria2Options::extra).
+    pub fn options(&self) -> Result<Aria2Options, ConfError> {
+        to_options(self.entries())
+    }
+
+    /// where the aria2 started with this file listens, on this host.
+    ///
+    /// `wss://` with `rpc-secure=true`, aria2's default port unless
+    /// `rpc-listen-port` is set. An absent `enable-rpc` is taken as given on the command line.
+    pub fn connection_meta(&self) -> Result<ConnectionMeta, ConfError> {
+        let options = self.options()?;
+        if options.enable_rpc == Some(false) {
+            return Err(ConfError::RpcDisabled);
+        }
+        let scheme = if options.rpc_secure == Some(true) { "wss" } else { "ws" };
+        let port = options.rpc_listen_port.unwrap_or(DEFAULT_PORT);
+        let secret = options.rpc_secret.as_ref().map(|secret| secret.expose());
+        Ok(ConnectionMeta::new(
+            &format!("{scheme}://localhost:{port}/jsonrpc"),
+            secret,
+        ))
+    }
+}
+
+impl FromStr for Aria2Conf {
+    type Err = ConfError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = parse_option(line, i + 1)?;
+            entries.push((key.to_string(), value.to_string()));
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// a `key=value` line, trimmed, at `line` counted from 1.
+pub(crate) fn parse_option(text: &str, line: usize) -> Result<(&str, &str), ConfError> {
+    let (key, value) = text.split_once('=').ok_or(ConfError::Syntax(line))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(ConfError::Syntax(line));
+    }
+    Ok((key, value.trim()))
+}
+
+/// `entries` as [`Aria2Options`], a later value replacing an earlier one.
+pub(crate) fn to_options<'a>(
+    entries: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<Aria2Options, ConfError> {
+    let mut map = Map::new();
+    for (key, value) in entries {
+        let value = Value::String(value.to_string());
+        if !CUMULATIVE.contains(&key) {
+            map.insert(key.to_string(), value);
+            continue;
+        }
+        match map.entry(key.to_string()).or_insert_with(|| Value::Array(Vec::new())) {
+            Value::Array(values) => values.push(value),
+            _ => unreachable!(),
+        }
+    }
+    serde_json::from_value(Value::Object(map)).map_err(ConfError::InvalidValue)
+}
+
+/// `key=value` pairs of `options`, a cumulative option once per value.
+pub(crate) fn from_options(options: &Aria2Options) -> serde_json::Result<Vec<(String, String)>> {
+    let Value::Object(map) = serde_json::to_value(options)? else {
+        return Ok(Vec::new());
+    };
+    let mut entries = Vec::with_capacity(map.len());
+    for (key, value) in map {
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                Value::String(value) => value,
+                value => value.to_string(),
+            };
+            entries.push((key.clone(), value));
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conf(s: &str) -> Aria2Conf {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let conf = conf("# dir=/nope\n\n  dir = /downloads \n\t# out=x\n");
+        assert_eq!(conf.entries().collect::<Vec<_>>(), [("dir", "/downloads")]);
+        assert!(matches!("dir=/d\nno value".parse::<Aria2Conf>(), Err(ConfError::Syntax(2))));
+        assert!(matches!("=x".parse::<Aria2Conf>(), Err(ConfError::Syntax(1))));
+    }
+
+    #[test]
+    fn last_value_wins() {
+        let conf = conf("dir=/a\ndir=/b");
+        assert_eq!(conf.get("dir"), Some("/b"));
+        assert_eq!(conf.options().unwrap().dir.as_deref(), Some("/b"));
+    }
+
+    #[test]
+    fn cumulative() {
+        let conf = conf("header=X-A: 1\nindex-out=1=a\nheader=X-B: 2\nindex-out=2=b");
+        assert_eq!(conf.get("header"), Some("X-B: 2"));
+        assert_eq!(conf.get_all("index-out").collect::<Vec<_>>(), ["1=a", "2=b"]);
+        let header = conf.options().unwrap().header;
+        assert_eq!(header, Some(vec!["X-A: 1".to_string(), "X-B: 2".to_string()]));
+
+        let options = Aria2Options {
+            header: Some(vec!["X-A: 1".into(), "X-B: 2".into()]),
+            ..Default::default()
+        };
+        let entries = from_options(&options).unwrap();
+        assert_eq!(entries, [("header".into(), "X-A: 1".into()), ("header".into(), "X-B: 2".into())]);
+    }
+
+    #[test]
+    fn booleans() {
+        let options = conf("enable-rpc=true\nrpc-secure=false").options().unwrap();
+        assert_eq!(options.enable_rpc, Some(true));
+        assert_eq!(options.rpc_secure, Some(false));
+        assert!(matches!(conf("enable-rpc=yes").options(), Err(ConfError::InvalidValue(_))));
+    }
+
+    #[test]
+    fn connection_meta() {
+        let meta = conf("enable-rpc=true").connection_meta().unwrap();
+        assert_eq!(meta.url, format!("ws://localhost:{DEFAULT_PORT}/jsonrpc"));
+        assert!(meta.token.is_none());
+
+        let meta = conf("rpc-secure=true\nrpc-listen-port=6801\nrpc-secret=s")
+            .connection_meta()
+            .unwrap();
+        assert_eq!(meta.url, "wss://localhost:6801/jsonrpc");
+        assert_eq!(meta.token.unwrap().expose(), "token:s");
+
+        assert!(matches!(conf("enable-rpc=false").connection_meta(), Err(ConfError::RpcDisabled)));
+    }
+}
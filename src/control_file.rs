@@ -0,0 +1,309 @@
+//! aria2's control files, the `<file>.aria2` kept next to a partial download
+//! to resume it.
+//!
+//! https://aria2.github.io/manual/en/html/technical-notes.html#control-file-aria2-format
+//!
+//! Read without aria2 running, the progress is the same [`Bitfield`] as
+//! [`TellStatusReply::pieces`](crate::call::TellStatusReply::pieces).
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::{Bitfield, ParseBitfieldError};
+
+/// size of the blocks an in-flight piece is downloaded by.
+const BLOCK_LENGTH: u64 = 16 * 1024;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseControlFileError {
+    #[error("unsupported control file version {0}")]
+    UnsupportedVersion(u16),
+    #[error("control file ends before its {0}")]
+    Truncated(&'static str),
+    #[error("invalid piece length 0")]
+    ZeroPieceLength,
+    #[error("{0}")]
+    Bitfield(#[from] ParseBitfieldError),
+}
+
+/// A piece partially downloaded, by blocks of 16 KiB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InFlightPiece {
+    pub index: u32,
+    pub length: u32,
+    /// blocks of the piece already written
+    pub blocks: Bitfield,
+}
+
+impl InFlightPiece {
+    pub fn completed_length(&self) -> u64 {
+        self.blocks.completed_length(BLOCK_LENGTH, self.length as u64)
+    }
+}
+
+/// Progress of a download as saved by aria2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFile {
+    /// the torrent's info hash, None for http, ftp and sftp downloads
+    pub info_hash: Option<Vec<u8>>,
+    pub piece_length: u32,
+    pub total_length: u64,
+    /// uploaded bytes, of torrents
+    pub upload_length: u64,
+    /// completed pieces
+    pub bitfield: Bitfield,
+    pub in_flight: Vec<InFlightPiece>,
+}
+
+impl ControlFile {
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&std::fs::read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// parse the content of a control file, version 1 or the legacy version 0
+    /// taken as written by a little endian host.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseControlFileError> {
+        let mut reader = Reader {
+            bytes,
+            big_endian: true,
+        };
+        let version = u16::from_be_bytes(reader.array("version")?);
+        match version {
+            0 => reader.big_endian = false,
+            1 => {}
+            v => return Err(ParseControlFileError::UnsupportedVersion(v)),
+        }
+        // only tells whether to check the info hash against the torrent
+        reader.take(4, "extension")?;
+        let info_hash_length = reader.u32("info hash length")?;
+        let info_hash = reader.take(info_hash_length as usize, "info hash")?;
+        let piece_length = reader.u32("piece length")?;
+        if piece_length == 0 {
+            return Err(ParseControlFileError::ZeroPieceLength);
+        }
+        let total_length = reader.u64("total length")?;
+        let upload_length = reader.u64("upload length")?;
+        let bitfield_length = reader.u32("bitfield length")?;
+        let bitfield = reader.take(bitfield_length as usize, "bitfield")?;
+        let num_pieces = total_length.div_ceil(piece_length as u64);
+        let bitfield = Bitfield::from_bytes(bitfield.to_vec(), num_pieces)?;
+
+        let num_in_flight = reader.u32("number of in-flight pieces")?;
+        let mut in_flight = Vec::new();
+        for _ in 0..num_in_flight {
+            let index = reader.u32("in-flight piece index")?;
+            let length = reader.u32("in-flight piece length")?;
+            let blocks_length = reader.u32("in-flight piece bitfield length")?;
+            let blocks = reader.take(blocks_length as usize, "in-flight piece bitfield")?;
+            let num_blocks = (length as u64).div_ceil(BLOCK_LENGTH);
+            in_flight.push(InFlightPiece {
+                index,
+                length,
+                blocks: Bitfield::from_bytes(blocks.to_vec(), num_blocks)?,
+            });
+        }
+
+        Ok(Self {
+            info_hash: (info_hash_length > 0).then(|| info_hash.to_vec()),
+            piece_length,
+            total_length,
+            upload_length,
+            bitfield,
+            in_flight,
+        })
+    }
+
+    /// write as version 1, the one aria2 writes.
+    pub fn write<W: Write>(&self, out: W) -> io::Result<()> {
+        self.write_version(out, true)
+    }
+
+    /// write as version 1, or as version 0 from a little endian host.
+    fn write_version<W: Write>(&self, out: W, big_endian: bool) -> io::Result<()> {
+        let mut writer = Writer { out, big_endian };
+        let info_hash = self.info_hash.as_deref().unwrap_or_default();
+        let version: u16 = if big_endian { 1 } else { 0 };
+        writer.out.write_all(&version.to_be_bytes())?;
+        writer.u32(if info_hash.is_empty() { 0 } else { 1 })?;
+        writer.bytes(info_hash)?;
+        writer.u32(self.piece_length)?;
+        writer.u64(self.total_length)?;
+        writer.u64(self.upload_length)?;
+        writer.bytes(self.bitfield.as_bytes())?;
+        writer.u32(self.in_flight.len() as u32)?;
+        for piece in &self.in_flight {
+            writer.u32(piece.index)?;
+            writer.u32(piece.length)?;
+            writer.bytes(piece.blocks.as_bytes())?;
+        }
+        writer.out.flush()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        // writing to a Vec does not fail
+        self.write(&mut out).expect("write control file");
+        out
+    }
+
+    /// bytes written, completed pieces and the blocks of in-flight ones.
+    pub fn completed_length(&self) -> u64 {
+        let piece_length = self.piece_length as u64;
+        let in_flight: u64 = self
+            .in_flight
+            .iter()
+            .filter(|piece| !self.bitfield.has(piece.index as u64))
+            .map(InFlightPiece::completed_length)
+            .sum();
+        self.bitfield.completed_length(piece_length, self.total_length) + in_flight
+    }
+}
+
+struct Writer<W> {
+    out: W,
+    big_endian: bool,
+}
+
+impl<W: Write> Writer<W> {
+    fn u32(&mut self, n: u32) -> io::Result<()> {
+        match self.big_endian {
+            true => self.out.write_all(&n.to_be_bytes()),
+            false => self.out.write_all(&n.to_le_bytes()),
+        }
+    }
+
+    fn u64(&mut self, n: u64) -> io::Result<()> {
+        match self.big_endian {
+            true => self.out.write_all(&n.to_be_bytes()),
+            false => self.out.write_all(&n.to_le_bytes()),
+        }
+    }
+
+    /// `bytes` preceded by their length.
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.u32(bytes.len() as u32)?;
+        self.out.write_all(bytes)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    /// the next `n` bytes, `what` names them in the error.
+    fn take(&mut self, n: usize, what: &'static str) -> Result<&'a [u8], ParseControlFileError> {
+        if self.bytes.len() < n {
+            return Err(ParseControlFileError::Truncated(what));
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self, what: &'static str) -> Result<[u8; N], ParseControlFileError> {
+        Ok(self.take(N, what)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self, what: &'static str) -> Result<u32, ParseControlFileError> {
+        let bytes = self.array(what)?;
+        Ok(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+
+    fn u64(&mut self, what: &'static str) -> Result<u64, ParseControlFileError> {
+        let bytes = self.array(what)?;
+        Ok(match self.big_endian {
+            true => u64::from_be_bytes(bytes),
+            false => u64::from_le_bytes(bytes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u32 = 1024 * 1024;
+
+    fn control_file() -> ControlFile {
+        ControlFile {
+            info_hash: Some((0..20).collect()),
+            piece_length: MIB,
+            // the last of the 4 pieces is half a piece
+            total_length: 3 * MIB as u64 + MIB as u64 / 2,
+            upload_length: 12345,
+            bitfield: Bitfield::from_bytes(vec![0b1010_0000], 4).unwrap(),
+            in_flight: vec![InFlightPiece {
+                index: 1,
+                length: MIB,
+                // the first 3 of 64 blocks
+                blocks: Bitfield::from_bytes(vec![0b1110_0000, 0, 0, 0, 0, 0, 0, 0], 64).unwrap(),
+            }],
+        }
+    }
+
+    fn to_bytes(control_file: &ControlFile, big_endian: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        control_file.write_version(&mut out, big_endian).unwrap();
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        let control_file = control_file();
+        let v1 = to_bytes(&control_file, true);
+        let v0 = to_bytes(&control_file, false);
+        assert_eq!(v1, control_file.to_bytes());
+        assert_eq!(v1[..2], [0, 1]);
+        assert_eq!(v0[..2], [0, 0]);
+        assert_ne!(v0[2..], v1[2..]);
+        assert_eq!(ControlFile::parse(&v1).unwrap(), control_file);
+        assert_eq!(ControlFile::parse(&v0).unwrap(), control_file);
+
+        let http = ControlFile {
+            info_hash: None,
+            in_flight: Vec::new(),
+            ..control_file
+        };
+        assert_eq!(ControlFile::parse(&http.to_bytes()).unwrap(), http);
+    }
+
+    #[test]
+    fn completed_length() {
+        // pieces 0 and 2, and 3 blocks of piece 1
+        let expected = 2 * MIB as u64 + 3 * BLOCK_LENGTH;
+        assert_eq!(control_file().completed_length(), expected);
+    }
+
+    #[test]
+    fn truncated() {
+        for big_endian in [true, false] {
+            let bytes = to_bytes(&control_file(), big_endian);
+            for end in 0..bytes.len() {
+                assert!(
+                    matches!(ControlFile::parse(&bytes[..end]), Err(ParseControlFileError::Truncated(_))),
+                    "{end} of {} bytes",
+                    bytes.len()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let mut bytes = control_file().to_bytes();
+        bytes[1] = 2;
+        assert_eq!(ControlFile::parse(&bytes), Err(ParseControlFileError::UnsupportedVersion(2)));
+
+        let bytes = ControlFile {
+            piece_length: 0,
+            ..control_file()
+        }
+        .to_bytes();
+        assert_eq!(ControlFile::parse(&bytes), Err(ParseControlFileError::ZeroPieceLength));
+    }
+}
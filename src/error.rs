@@ -1,4 +1,5 @@
 use crate::jsonrpc;
+use crate::Gid;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -9,13 +10,26 @@ pub enum Error {
     #[error("Decode error {0}")]
     Encode(serde_json::Error),
     #[error("Connect error {0}")]
-    Connect(tokio_tungstenite::tungstenite::error::Error),
+    Connect(tungstenite::Error),
     #[error("Request send error")]
     ChannelSend,
     #[error("Response send error {0}")]
     ChannelRecv(#[from] tokio::sync::oneshot::error::RecvError),
     #[error("Websocket error {0}")]
-    Websocket(#[from] tokio_tungstenite::tungstenite::Error),
+    Websocket(#[from] tungstenite::Error),
+    #[error("Io error {0}")]
+    Io(std::io::Error),
+    /// the background task panicked, or was dropped along with its runtime
+    #[error("Background task panicked")]
+    BackgroundPanicked,
+}
+
+impl Error {
+    /// the link to aria2 broke before the response came, the call may or may
+    /// not have been carried out.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Error::ChannelRecv(_) | Error::Websocket(_))
+    }
 }
 
 
@@ -25,6 +39,67 @@ pub struct RpcError {
     pub message: String,
 }
 
+/// What an [`RpcError`] is about, parsed from its code and message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RpcErrorKind {
+    /// the secret token is missing or wrong
+    Unauthorized,
+    /// `GID <gid> is not found`
+    GidNotFound(Gid),
+    /// `Invalid GID <gid>`
+    InvalidGid(String),
+    /// an option was rejected, carries the option name
+    InvalidOption(String),
+    /// -32700, invalid JSON was received by the server
+    ParseError,
+    /// -32600, the JSON sent is not a valid Request object
+    InvalidRequest,
+    /// -32601, the method does not exist / is not available
+    MethodNotFound,
+    /// -32602, invalid method parameter(s)
+    InvalidParams,
+    /// -32603, internal JSON-RPC error
+    InternalError,
+    Other,
+}
+
+impl RpcError {
+    pub fn kind(&self) -> RpcErrorKind {
+        match self.code {
+            -32700 => return RpcErrorKind::ParseError,
+            -32600 => return RpcErrorKind::InvalidRequest,
+            -32601 => return RpcErrorKind::MethodNotFound,
+            -32602 => return RpcErrorKind::InvalidParams,
+            -32603 => return RpcErrorKind::InternalError,
+            _ => {}
+        }
+        let message = self.message.trim_end_matches('.');
+        if message == "Unauthorized" {
+            return RpcErrorKind::Unauthorized;
+        }
+        if let Some(gid) = message
+            .strip_prefix("GID ")
+            .and_then(|s| s.strip_suffix(" is not found"))
+            .and_then(|s| s.parse().ok())
+        {
+            return RpcErrorKind::GidNotFound(gid);
+        }
+        if let Some(gid) = message.strip_prefix("Invalid GID ") {
+            return RpcErrorKind::InvalidGid(gid.to_string());
+        }
+        // We encountered a problem while processing the option '--dir'.
+        if let Some(option) = message
+            .split_once("option '")
+            .and_then(|(_, rest)| rest.split_once('\''))
+            .map(|(option, _)| option.trim_start_matches("--"))
+        {
+            return RpcErrorKind::InvalidOption(option.to_string());
+        }
+        RpcErrorKind::Other
+    }
+}
+
 impl std::fmt::Display for RpcError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -43,4 +118,44 @@ impl From<jsonrpc::Error> for Error {
             message: err.message,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(code: i64, message: &str) -> RpcErrorKind {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+        .kind()
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(kind(1, "Unauthorized"), RpcErrorKind::Unauthorized);
+        assert_eq!(
+            kind(1, "GID 2089b05ecca3d829 is not found"),
+            RpcErrorKind::GidNotFound("2089b05ecca3d829".parse().unwrap())
+        );
+        assert_eq!(
+            kind(1, "Invalid GID xyz"),
+            RpcErrorKind::InvalidGid("xyz".to_string())
+        );
+        assert_eq!(
+            kind(1, "We encountered a problem while processing the option '--dir'."),
+            RpcErrorKind::InvalidOption("dir".to_string())
+        );
+        assert_eq!(kind(1, "No such download"), RpcErrorKind::Other);
+    }
+
+    #[test]
+    fn json_rpc_codes() {
+        assert_eq!(kind(-32700, "Parse error."), RpcErrorKind::ParseError);
+        assert_eq!(kind(-32600, "Invalid Request."), RpcErrorKind::InvalidRequest);
+        assert_eq!(kind(-32601, "Method not found."), RpcErrorKind::MethodNotFound);
+        assert_eq!(kind(-32602, "Invalid params."), RpcErrorKind::InvalidParams);
+        assert_eq!(kind(-32603, "Internal error."), RpcErrorKind::InternalError);
+    }
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+
+/// https://aria2.github.io/manual/en/html/aria2c.html#exit-status
+///
+/// Reason a download stopped, reported as `errorCode` by `aria2.tellStatus`.
+/// Codes unknown to this version of the crate are kept as `Other`.
+///
+/// Compared by [`code`](DownloadErrorCode::code), `Other(3)` is equal to
+/// `ResourceNotFound`.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum DownloadErrorCode {
+    /// All downloads were successful.
+    Finished,
+    /// An unknown error occurred.
+    UnknownError,
+    /// Time out occurred.
+    Timeout,
+    /// A resource was not found.
+    ResourceNotFound,
+    /// aria2 saw the specified number of "resource not found" error, see `--max-file-not-found`.
+    MaxFileNotFound,
+    /// A download aborted because download speed was too slow, see `--lowest-speed-limit`.
+    TooSlowDownloadSpeed,
+    /// Network problem occurred.
+    NetworkProblem,
+    /// There were unfinished downloads when aria2 exited.
+    InProgress,
+    /// Remote server did not support resume when resume was required.
+    CannotResume,
+    /// There was not enough disk space available.
+    NotEnoughDiskSpace,
+    /// The piece length was different from the one in the .aria2 control file, see `--allow-piece-length-change`.
+    PieceLengthChanged,
+    /// aria2 was downloading same file at that moment.
+    DuplicateDownload,
+    /// aria2 was downloading same info hash torrent at that moment.
+    DuplicateInfoHash,
+    /// File already existed, see `--allow-overwrite`.
+    FileAlreadyExists,
+    /// Renaming file failed, see `--auto-file-renaming`.
+    FileRenamingFailed,
+    /// aria2 could not open existing file.
+    FileOpenError,
+    /// aria2 could not create new file or truncate existing file.
+    FileCreateError,
+    /// File I/O error occurred.
+    FileIoError,
+    /// aria2 could not create directory.
+    DirCreateError,
+    /// Name resolution failed.
+    NameResolveError,
+    /// aria2 could not parse Metalink document.
+    MetalinkParseError,
+    /// FTP command failed.
+    FtpProtocolError,
+    /// HTTP response header was bad or unexpected.
+    HttpProtocolError,
+    /// Too many redirects occurred.
+    HttpTooManyRedirects,
+    /// HTTP authorization failed.
+    HttpAuthFailed,
+    /// aria2 could not parse bencoded file (usually ".torrent" file).
+    BencodeParseError,
+    /// ".torrent" file was corrupted or missing information that aria2 needed.
+    BittorrentParseError,
+    /// Magnet URI was bad.
+    MagnetParseError,
+    /// Bad/unrecognized option was given or unexpected option argument was given.
+    OptionError,
+    /// The remote server was unable to handle the request due to a temporary overloading or maintenance.
+    HttpServiceUnavailable,
+    /// aria2 could not parse JSON-RPC request.
+    JsonParseError,
+    /// Reserved. Not used.
+    Reserved,
+    /// Checksum validation failed.
+    ChecksumError,
+    Other(u32),
+}
+
+impl DownloadErrorCode {
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            0 => Self::Finished,
+            1 => Self::UnknownError,
+            2 => Self::Timeout,
+            3 => Self::ResourceNotFound,
+            4 => Self::MaxFileNotFound,
+            5 => Self::TooSlowDownloadSpeed,
+            6 => Self::NetworkProblem,
+            7 => Self::InProgress,
+            8 => Self::CannotResume,
+            9 => Self::NotEnoughDiskSpace,
+            10 => Self::PieceLengthChanged,
+            11 => Self::DuplicateDownload,
+            12 => Self::DuplicateInfoHash,
+            13 => Self::FileAlreadyExists,
+            14 => Self::FileRenamingFailed,
+            15 => Self::FileOpenError,
+            16 => Self::FileCreateError,
+            17 => Self::FileIoError,
+            18 => Self::DirCreateError,
+            19 => Self::NameResolveError,
+            20 => Self::MetalinkParseError,
+            21 => Self::FtpProtocolError,
+            22 => Self::HttpProtocolError,
+            23 => Self::HttpTooManyRedirects,
+            24 => Self::HttpAuthFailed,
+            25 => Self::BencodeParseError,
+            26 => Self::BittorrentParseError,
+            27 => Self::MagnetParseError,
+            28 => Self::OptionError,
+            29 => Self::HttpServiceUnavailable,
+            30 => Self::JsonParseError,
+            31 => Self::Reserved,
+            32 => Self::ChecksumError,
+            other => Self::Other(other),
+        }
+    }
+
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::Finished => 0,
+            Self::UnknownError => 1,
+            Self::Timeout => 2,
+            Self::ResourceNotFound => 3,
+            Self::MaxFileNotFound => 4,
+            Self::TooSlowDownloadSpeed => 5,
+            Self::NetworkProblem => 6,
+            Self::InProgress => 7,
+            Self::CannotResume => 8,
+            Self::NotEnoughDiskSpace => 9,
+            Self::PieceLengthChanged => 10,
+            Self::DuplicateDownload => 11,
+            Self::DuplicateInfoHash => 12,
+            Self::FileAlreadyExists => 13,
+            Self::FileRenamingFailed => 14,
+            Self::FileOpenError => 15,
+            Self::FileCreateError => 16,
+            Self::FileIoError => 17,
+            Self::DirCreateError => 18,
+            Self::NameResolveError => 19,
+            Self::MetalinkParseError => 20,
+            Self::FtpProtocolError => 21,
+            Self::HttpProtocolError => 22,
+            Self::HttpTooManyRedirects => 23,
+            Self::HttpAuthFailed => 24,
+            Self::BencodeParseError => 25,
+            Self::BittorrentParseError => 26,
+            Self::MagnetParseError => 27,
+            Self::OptionError => 28,
+            Self::HttpServiceUnavailable => 29,
+            Self::JsonParseError => 30,
+            Self::Reserved => 31,
+            Self::ChecksumError => 32,
+            Self::Other(code) => *code,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Finished)
+    }
+
+    /// failures caused by the network or the remote server.
+    pub fn is_network(&self) -> bool {
+        matches!(
+            self,
+            Self::Timeout
+                | Self::ResourceNotFound
+                | Self::MaxFileNotFound
+                | Self::TooSlowDownloadSpeed
+                | Self::NetworkProblem
+                | Self::CannotResume
+                | Self::NameResolveError
+                | Self::FtpProtocolError
+                | Self::HttpProtocolError
+                | Self::HttpTooManyRedirects
+                | Self::HttpAuthFailed
+                | Self::HttpServiceUnavailable
+        )
+    }
+
+    /// failures caused by the local file system.
+    pub fn is_disk(&self) -> bool {
+        matches!(
+            self,
+            Self::NotEnoughDiskSpace
+                | Self::FileAlreadyExists
+                | Self::FileRenamingFailed
+                | Self::FileOpenError
+                | Self::FileCreateError
+                | Self::FileIoError
+                | Self::DirCreateError
+        )
+    }
+
+    /// failures likely to be gone when the download is simply added again,
+    /// as opposed to those needing a change of uri, options or disk state.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::UnknownError
+                | Self::Timeout
+                | Self::TooSlowDownloadSpeed
+                | Self::NetworkProblem
+                | Self::InProgress
+                | Self::NameResolveError
+                | Self::HttpServiceUnavailable
+        )
+    }
+}
+
+impl fmt::Display for DownloadErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Other(code) => write!(f, "error code {code}"),
+            _ => write!(f, "{self:?}({})", self.code()),
+        }
+    }
+}
+
+impl PartialEq for DownloadErrorCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.code() == other.code()
+    }
+}
+
+impl Eq for DownloadErrorCode {}
+
+impl std::hash::Hash for DownloadErrorCode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.code().hash(state);
+    }
+}
+
+impl From<u32> for DownloadErrorCode {
+    fn from(code: u32) -> Self {
+        Self::from_code(code)
+    }
+}
+
+/// aria2 sends the code as a string
+impl<'de> serde::Deserialize<'de> for DownloadErrorCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse::<u32>()
+            .map(Self::from_code)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_round_trip() {
+        for code in 0..40 {
+            assert_eq!(DownloadErrorCode::from_code(code).code(), code);
+        }
+        assert_eq!(DownloadErrorCode::from_code(3), DownloadErrorCode::ResourceNotFound);
+        assert_eq!(DownloadErrorCode::from_code(33), DownloadErrorCode::Other(33));
+    }
+
+    #[test]
+    fn other_equals_its_known_code() {
+        assert_eq!(DownloadErrorCode::Other(3), DownloadErrorCode::ResourceNotFound);
+        assert_ne!(DownloadErrorCode::Other(4), DownloadErrorCode::ResourceNotFound);
+        let set: std::collections::HashSet<_> =
+            [DownloadErrorCode::Other(3), DownloadErrorCode::ResourceNotFound].into();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn deserialized_from_a_string() {
+        let code: DownloadErrorCode = serde_json::from_str("\"9\"").unwrap();
+        assert_eq!(code, DownloadErrorCode::NotEnoughDiskSpace);
+        let code: DownloadErrorCode = serde_json::from_str("\"99\"").unwrap();
+        assert_eq!(code, DownloadErrorCode::Other(99));
+        assert!(serde_json::from_str::<DownloadErrorCode>("9").is_err());
+        assert!(serde_json::from_str::<DownloadErrorCode>("\"nine\"").is_err());
+    }
+}
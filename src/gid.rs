@@ -0,0 +1,159 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// https://aria2.github.io/manual/en/html/aria2c.html#terminology
+///
+/// aria2 identifies each download by a GID, a 64 bit number represented as a
+/// hex string of 16 characters.
+///
+/// Use [`str::parse`] to get a validated `Gid`. The `From<&str>` and
+/// `From<String>` conversions lowercase the text like `parse` but do not
+/// check it, see [`Gid::is_valid`]; a malformed one is reported by aria2
+/// when used.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+#[serde(transparent)]
+pub struct Gid(String);
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid gid {0:?}, expect a hex string of 16 characters")]
+pub struct ParseGidError(String);
+
+impl Gid {
+    /// gid for a 64 bit number, can be assigned to a new download through
+    /// [`Aria2Options::gid`](crate::options::Aria2Options::gid).
+    pub fn new(id: u64) -> Self {
+        Self(format!("{id:016x}"))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.0.len() == 16 && self.0.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+
+    pub fn to_u64(&self) -> Result<u64, ParseGidError> {
+        if !self.is_valid() {
+            return Err(ParseGidError(self.0.clone()));
+        }
+        u64::from_str_radix(&self.0, 16).map_err(|_| ParseGidError(self.0.clone()))
+    }
+}
+
+impl FromStr for Gid {
+    type Err = ParseGidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let gid = Self(s.to_ascii_lowercase());
+        if gid.is_valid() {
+            Ok(gid)
+        } else {
+            Err(ParseGidError(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for Gid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for Gid {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<u64> for Gid {
+    fn from(id: u64) -> Self {
+        Self::new(id)
+    }
+}
+
+impl TryFrom<&Gid> for u64 {
+    type Error = ParseGidError;
+
+    fn try_from(gid: &Gid) -> Result<Self, Self::Error> {
+        gid.to_u64()
+    }
+}
+
+impl TryFrom<Gid> for u64 {
+    type Error = ParseGidError;
+
+    fn try_from(gid: Gid) -> Result<Self, Self::Error> {
+        gid.to_u64()
+    }
+}
+
+impl From<&str> for Gid {
+    fn from(s: &str) -> Self {
+        Self::from(s.to_string())
+    }
+}
+
+impl From<String> for Gid {
+    fn from(mut s: String) -> Self {
+        s.make_ascii_lowercase();
+        Self(s)
+    }
+}
+
+impl From<&Gid> for Gid {
+    fn from(gid: &Gid) -> Self {
+        gid.clone()
+    }
+}
+
+impl From<Gid> for String {
+    fn from(gid: Gid) -> Self {
+        gid.0
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Gid {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_matches_parse() {
+        let parsed: Gid = "ABCDEF0123456789".parse().unwrap();
+        assert_eq!(Gid::from("ABCDEF0123456789"), parsed);
+        assert_eq!(Gid::from(String::from("abcdef0123456789")), parsed);
+        assert_eq!(parsed.as_str(), "abcdef0123456789");
+    }
+
+    #[test]
+    fn from_is_unchecked() {
+        let gid = Gid::from("Not-A-Gid");
+        assert_eq!(gid.as_str(), "not-a-gid");
+        assert!(!gid.is_valid());
+        assert!(gid.to_u64().is_err());
+    }
+
+    #[test]
+    fn parse_rejects_malformed() {
+        assert!("2089b05ecca3d82".parse::<Gid>().is_err());
+        assert!("2089b05ecca3d829a".parse::<Gid>().is_err());
+        assert!("2089b05ecca3d82g".parse::<Gid>().is_err());
+    }
+
+    #[test]
+    fn u64_round_trip() {
+        let gid = Gid::new(0x2089b05ecca3d829);
+        assert_eq!(gid.as_str(), "2089b05ecca3d829");
+        assert_eq!(gid.to_u64(), Ok(0x2089b05ecca3d829));
+    }
+}
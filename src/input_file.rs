@@ -0,0 +1,190 @@
+//! aria2's input file, as given to `--input-file` and written by
+//! `--save-session`.
+//!
+//! ```text
+//! # comment
+//! http://mirror-a/file.iso<TAB>http://mirror-b/file.iso
+//!  dir=/downloads
+//!  out=file.iso
+//! http://example.com/b.zip
+//! ```
+//!
+//! A line of tab-separated uris starts a download, all pointing to the same
+//! file. The indented `option=value` lines after it are its options, the same
+//! as in the [configuration file](crate::conf).
+//!
+//! Each download maps to an [`AddUri`], so a saved session can be added to a
+//! running aria2 and its queue saved back. Options without a field in
+//! [`Aria2Options`](crate::options::Aria2Options) are kept in its `extra`,
+//! the `position` of a call is not written.
+//!
+//! The queue of a running aria2 is exported with [`Client::export_session`].
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use futures_util::StreamExt;
+
+use crate::call::{AddUri, GetOption, TaskStatus, TellActive, TellStatusField};
+use crate::conf::{self, ConfError};
+use crate::{Client, RpcErrorKind};
+
+/// a download as written, its options not parsed yet.
+struct Entry<'a> {
+    uris: Vec<String>,
+    options: Vec<(&'a str, &'a str)>,
+}
+
+pub fn parse(s: &str) -> Result<Vec<AddUri>, ConfError> {
+    let mut downloads: Vec<Entry> = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with([' ', '\t']) {
+            let option = conf::parse_option(line, i + 1)?;
+            let download = downloads.last_mut().ok_or(ConfError::Syntax(i + 1))?;
+            download.options.push(option);
+            continue;
+        }
+        let uris = line
+            .split('\t')
+            .map(str::trim)
+            .filter(|uri| !uri.is_empty())
+            .map(String::from)
+            .collect();
+        downloads.push(Entry {
+            uris,
+            options: Vec::new(),
+        });
+    }
+    downloads
+        .into_iter()
+        .map(|download| {
+            let options = match download.options.is_empty() {
+                true => None,
+                false => Some(conf::to_options(download.options)?),
+            };
+            Ok(AddUri::uris(download.uris).options(options))
+        })
+        .collect()
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<AddUri>> {
+    parse(&std::fs::read_to_string(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// write `downloads` in the input file format, readable by aria2 and [`read`].
+pub fn write<W: Write>(mut out: W, downloads: &[AddUri]) -> io::Result<()> {
+    for download in downloads {
+        writeln!(out, "{}", download.uris.join("\t"))?;
+        if let Some(options) = &download.options {
+            for (key, value) in conf::from_options(options)? {
+                writeln!(out, " {key}={value}")?;
+            }
+        }
+    }
+    out.flush()
+}
+
+pub fn to_string(downloads: &[AddUri]) -> String {
+    let mut out = Vec::new();
+    // writing to a Vec does not fail, neither does serializing the options
+    write(&mut out, downloads).expect("write input file");
+    String::from_utf8(out).expect("input file is utf-8")
+}
+
+impl Client {
+    /// the active and waiting downloads as [`AddUri`] calls, to be saved with
+    /// [`write`] as `--save-session` would.
+    ///
+    /// Each keeps its gid, and every option aria2 reports for it. Paused
+    /// downloads get `pause=true`, and downloads without uris, such as
+    /// torrents added from a file, are left out.
+    pub async fn export_session(&self) -> crate::Result<Vec<AddUri>> {
+        let fields = [
+            TellStatusField::Gid,
+            TellStatusField::Status,
+            TellStatusField::Files,
+        ];
+        let mut downloads = self.call(TellActive::new_with_fields(fields)).await?;
+        let mut waiting = Box::pin(self.waiting_stream(Some(fields)));
+        while let Some(download) = waiting.next().await {
+            downloads.push(download?);
+        }
+
+        let mut session = Vec::with_capacity(downloads.len());
+        for download in downloads {
+            let Some(gid) = download.gid else {
+                continue;
+            };
+            let mut uris: Vec<String> = Vec::new();
+            for uri in download.files.iter().flatten().flat_map(|file| &file.uris) {
+                if !uris.contains(&uri.uri) {
+                    uris.push(uri.uri.clone());
+                }
+            }
+            if uris.is_empty() {
+                continue;
+            }
+            let mut options = match self.call(GetOption::new(gid.clone())).await {
+                Ok(options) => options,
+                // removed since the queue was read
+                Err(crate::Error::Rpc(e)) if matches!(e.kind(), RpcErrorKind::GidNotFound(_)) => {
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+            options.gid = Some(gid);
+            if download.status == Some(TaskStatus::Paused) {
+                options.extra.insert("pause".to_string(), "true".to_string());
+            }
+            session.push(AddUri::uris(uris).options(Some(options)));
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// as written by `--save-session`, options in the order they are written back
+    const SESSION: &str = "\
+http://a/x.iso\thttp://b/x.iso
+ dir=/downloads
+ gid=2089b05ecca3d829
+ pause=true
+ select-file=1-3,5
+ split=4
+http://c/y.zip
+";
+
+    #[test]
+    fn save_session_round_trip() {
+        let downloads = parse(SESSION).unwrap();
+        assert_eq!(downloads.len(), 2);
+        assert_eq!(downloads[0].uris, ["http://a/x.iso", "http://b/x.iso"]);
+        let options = downloads[0].options.as_ref().unwrap();
+        assert_eq!(options.dir.as_deref(), Some("/downloads"));
+        assert_eq!(options.gid.as_ref().map(|gid| gid.as_str()), Some("2089b05ecca3d829"));
+        assert_eq!(options.extra["pause"], "true");
+        assert_eq!(options.extra["select-file"], "1-3,5");
+        assert_eq!(options.extra["split"], "4");
+        assert!(downloads[1].options.is_none());
+
+        assert_eq!(to_string(&downloads), SESSION);
+    }
+
+    #[test]
+    fn comments_and_errors() {
+        let downloads = parse("# saved\n\nhttp://a/x\n\tindex-out=1=a\n\tindex-out=2=b\n").unwrap();
+        let options = downloads[0].options.as_ref().unwrap();
+        assert_eq!(options.index_out, Some(vec!["1=a".to_string(), "2=b".to_string()]));
+        assert_eq!(to_string(&downloads), "http://a/x\n index-out=1=a\n index-out=2=b\n");
+
+        assert!(matches!(parse(" dir=/d\n"), Err(ConfError::Syntax(1))));
+        assert!(matches!(parse("http://a/x\n dir\n"), Err(ConfError::Syntax(2))));
+    }
+}
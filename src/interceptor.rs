@@ -0,0 +1,91 @@
+//! Hooks around every call of a [`Client`](crate::Client).
+//!
+//! An [`Interceptor`] gets each request before it is sent, can change its
+//! method and params, and sees what came back:
+//!
+//! ```ignore
+//! use aria2_rs_yet::interceptor::{BoxFuture, Interceptor, Next, Request};
+//!
+//! struct Audit;
+//!
+//! impl Interceptor for Audit {
+//!     fn intercept<'a>(
+//!         &'a self,
+//!         request: Request,
+//!         next: Next<'a>,
+//!     ) -> BoxFuture<'a, Result<serde_json::Value>> {
+//!         Box::pin(async move {
+//!             let method = request.method.clone();
+//!             let result = next.run(request).await;
+//!             println!("{method}: {}", if result.is_ok() { "ok" } else { "failed" });
+//!             result
+//!         })
+//!     }
+//! }
+//!
+//! let (client, _) = Client::builder(meta).interceptor(Audit).connect().await?;
+//! ```
+//!
+//! Interceptors run in the order they were added, the first one outermost.
+//! The requests the client makes on its own, to reconcile after reconnecting,
+//! do not go through them.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::limit::Priority;
+use crate::ws::ClientInner;
+use crate::Result;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A call on its way to aria2.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// positional params, without the secret token
+    pub params: Vec<Value>,
+    /// whether the secret token is put first when sent
+    pub authenticated: bool,
+    /// whether the call can be sent again, see [`Call::IDEMPOTENT`](crate::call::Call::IDEMPOTENT)
+    pub idempotent: bool,
+    /// see [`Call::PRIORITY`](crate::call::Call::PRIORITY)
+    pub priority: Priority,
+}
+
+pub trait Interceptor: Send + Sync + 'static {
+    /// handle `request`, usually by passing it on to `next`.
+    fn intercept<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Value>>;
+}
+
+impl<I: Interceptor + ?Sized> Interceptor for Arc<I> {
+    fn intercept<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+        (**self).intercept(request, next)
+    }
+}
+
+/// The rest of the chain, ending with sending the request.
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    chain: &'a [Arc<dyn Interceptor>],
+    client: &'a ClientInner,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(chain: &'a [Arc<dyn Interceptor>], client: &'a ClientInner) -> Self {
+        Self { chain, client }
+    }
+
+    /// pass `request` to the next interceptor, or send it if there is none.
+    ///
+    /// Can be called more than once, each time sending the request anew.
+    pub fn run(self, request: Request) -> BoxFuture<'a, Result<Value>> {
+        match self.chain.split_first() {
+            Some((first, rest)) => first.intercept(request, Next::new(rest, self.client)),
+            None => Box::pin(self.client.send(request)),
+        }
+    }
+}
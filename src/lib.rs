@@ -1,6 +1,30 @@
+extern crate self as aria2_rs_yet;
+
+mod api;
+mod bitfield;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod call;
+pub mod cassette;
+pub mod conf;
+pub mod control_file;
 mod error;
+mod error_code;
+mod gid;
+pub mod input_file;
+pub mod interceptor;
+pub mod limit;
+#[cfg(feature = "mock")]
+pub mod mock;
 pub mod options;
+pub mod projection;
+mod protocol;
+pub mod retry;
+mod runtime;
+mod secret;
+pub mod stream;
+#[cfg(feature = "testkit")]
+pub mod testkit;
 mod ws;
 
 /// https://www.jsonrpc.org/specification
@@ -31,8 +55,20 @@ mod jsonrpc {
 }
 
 
-pub use error::Error;
-pub use ws::{Client, ConnectionMeta, Notification};
+pub use api::Aria2Api;
+pub use bitfield::{Bitfield, ParseBitfieldError};
+pub use error::{Error, RpcError, RpcErrorKind};
+pub use error_code::DownloadErrorCode;
+pub use gid::{Gid, ParseGidError};
+pub use secret::Secret;
+pub use protocol::Notification;
+pub use ws::{Client, ClientBuilder, ConnectionMeta, ParseConnectionMetaError};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+    pub use serde_json;
+}
 
 pub type Result<T> = std::result::Result<T, Error>;
 
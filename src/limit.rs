@@ -0,0 +1,313 @@
+//! Client side throttling of calls, so bulk work does not flood aria2, which
+//! answers rpc on a single thread.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+use crate::interceptor::{BoxFuture, Interceptor, Next, Request};
+use crate::Result;
+
+/// Which calls go first when several wait for a [`RateLimit`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// calls someone waits on, such as `aria2.tellStatus`
+    #[default]
+    Interactive,
+    /// calls made in large numbers, such as `aria2.addUri`
+    Bulk,
+}
+
+/// Limits of the calls sent to aria2, set with
+/// [`ClientBuilder::rate_limit`](crate::ClientBuilder::rate_limit).
+///
+/// A call waiting for its turn is sent after every waiting call of a higher
+/// [`Priority`], and after the calls of its own priority that came first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimit {
+    /// calls per second and burst size
+    rate: Option<(f64, f64)>,
+    max_in_flight: Option<usize>,
+}
+
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// at most `rate` calls per second on average, in bursts of up to `burst`.
+    pub fn per_second(mut self, rate: f64, burst: u32) -> Self {
+        self.rate = Some((rate.max(f64::MIN_POSITIVE), burst.max(1) as f64));
+        self
+    }
+
+    /// at most `max` calls sent and not answered yet, at least 1.
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = Some(max.max(1));
+        self
+    }
+}
+
+struct State {
+    tokens: f64,
+    refilled: Instant,
+    in_flight: usize,
+    /// one queue per [`Priority`], in order
+    waiting: [VecDeque<oneshot::Sender<Permit>>; 2],
+    /// a task is sleeping until the next token
+    timer: bool,
+}
+
+impl State {
+    fn refill(&mut self, limit: &RateLimit) {
+        let now = Instant::now();
+        if let Some((rate, burst)) = limit.rate {
+            let elapsed = now.duration_since(self.refilled).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate).min(burst);
+        }
+        self.refilled = now;
+    }
+
+    fn has_slot(&self, limit: &RateLimit) -> bool {
+        limit.max_in_flight.is_none_or(|max| self.in_flight < max)
+    }
+
+    /// time until a token is available, if there is none now.
+    fn token_wait(&self, limit: &RateLimit) -> Option<Duration> {
+        let (rate, _) = limit.rate?;
+        (self.tokens < 1.0).then(|| Duration::from_secs_f64((1.0 - self.tokens) / rate))
+    }
+
+    fn take(&mut self, limit: &RateLimit) {
+        if limit.rate.is_some() {
+            self.tokens -= 1.0;
+        }
+        self.in_flight += 1;
+    }
+
+    fn give_back(&mut self, limit: &RateLimit) {
+        if limit.rate.is_some() {
+            self.tokens += 1.0;
+        }
+        self.in_flight -= 1;
+    }
+}
+
+struct Shared {
+    limit: RateLimit,
+    state: Mutex<State>,
+}
+
+impl Shared {
+    /// hand out permits to the waiting calls, as far as the limits allow.
+    fn dispatch(self: &Arc<Self>, state: &mut State) {
+        state.refill(&self.limit);
+        while let Some(lane) = state.waiting.iter().position(|queue| !queue.is_empty()) {
+            // a released permit dispatches again
+            if !state.has_slot(&self.limit) {
+                return;
+            }
+            if let Some(wait) = state.token_wait(&self.limit) {
+                if !state.timer {
+                    state.timer = true;
+                    let shared = self.clone();
+                    crate::runtime::spawn(async move {
+                        crate::runtime::sleep(wait).await;
+                        let mut state = shared.state.lock().unwrap();
+                        state.timer = false;
+                        shared.dispatch(&mut state);
+                    });
+                }
+                return;
+            }
+            let waiter = state.waiting[lane].pop_front().unwrap();
+            state.take(&self.limit);
+            if let Err(mut permit) = waiter.send(Permit(Some(self.clone()))) {
+                // the call was dropped while waiting
+                permit.0 = None;
+                state.give_back(&self.limit);
+            }
+        }
+    }
+}
+
+/// A slot taken by a call in flight, released on drop.
+struct Permit(Option<Arc<Shared>>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(shared) = self.0.take() {
+            let mut state = shared.state.lock().unwrap();
+            state.in_flight -= 1;
+            shared.dispatch(&mut state);
+        }
+    }
+}
+
+/// The interceptor enforcing a [`RateLimit`].
+pub(crate) struct Limiter(Arc<Shared>);
+
+impl Limiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        let state = State {
+            tokens: limit.rate.map_or(0.0, |(_, burst)| burst),
+            refilled: Instant::now(),
+            in_flight: 0,
+            waiting: Default::default(),
+            timer: false,
+        };
+        Self(Arc::new(Shared {
+            limit,
+            state: Mutex::new(state),
+        }))
+    }
+
+    async fn acquire(&self, priority: Priority) -> Permit {
+        let rx = {
+            let shared = &self.0;
+            let mut state = shared.state.lock().unwrap();
+            state.refill(&shared.limit);
+            let idle = state.waiting.iter().all(VecDeque::is_empty);
+            if idle && state.has_slot(&shared.limit) && state.token_wait(&shared.limit).is_none() {
+                state.take(&shared.limit);
+                return Permit(Some(shared.clone()));
+            }
+            let (tx, rx) = oneshot::channel();
+            state.waiting[priority as usize].push_back(tx);
+            shared.dispatch(&mut state);
+            rx
+        };
+        // the sender is only dropped along with the client
+        rx.await.unwrap_or(Permit(None))
+    }
+}
+
+impl Interceptor for Limiter {
+    fn intercept<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+        Box::pin(async move {
+            let _permit = self.acquire(request.priority).await;
+            next.run(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::FutureExt;
+
+    use super::*;
+
+    fn in_flight(limiter: &Limiter) -> usize {
+        limiter.0.state.lock().unwrap().in_flight
+    }
+
+    fn waiting(limiter: &Limiter, priority: Priority) -> usize {
+        limiter.0.state.lock().unwrap().waiting[priority as usize].len()
+    }
+
+    #[tokio::test]
+    async fn burst_then_refill() {
+        let limiter = Limiter::new(RateLimit::new().per_second(20.0, 5));
+        let started = Instant::now();
+        for _ in 0..5 {
+            limiter.acquire(Priority::Interactive).await;
+        }
+        assert!(started.elapsed() < Duration::from_millis(40));
+        // 10 more at 20 per second
+        for _ in 0..10 {
+            limiter.acquire(Priority::Interactive).await;
+        }
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(450), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(900), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn timer_restarts_once_emptied() {
+        let limiter = Limiter::new(RateLimit::new().per_second(20.0, 1));
+        for _ in 0..2 {
+            let started = Instant::now();
+            // one from the burst, then one every 50ms, each waiting for the timer
+            let (a, b, c) = tokio::join!(
+                limiter.acquire(Priority::Interactive),
+                limiter.acquire(Priority::Interactive),
+                limiter.acquire(Priority::Interactive),
+            );
+            drop((a, b, c));
+            let elapsed = started.elapsed();
+            assert!(elapsed >= Duration::from_millis(90), "{elapsed:?}");
+            assert!(elapsed < Duration::from_millis(400), "{elapsed:?}");
+            assert!(!limiter.0.state.lock().unwrap().timer);
+            // the bucket fills up again
+            tokio::time::sleep(Duration::from_millis(60)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn max_in_flight() {
+        let limiter = Limiter::new(RateLimit::new().max_in_flight(2));
+        let first = limiter.acquire(Priority::Interactive).await;
+        let _second = limiter.acquire(Priority::Interactive).await;
+        let mut third = Box::pin(limiter.acquire(Priority::Interactive));
+        assert!((&mut third).now_or_never().is_none());
+        assert_eq!(in_flight(&limiter), 2);
+
+        drop(first);
+        let _third = third.now_or_never().expect("a slot was released");
+        assert_eq!(in_flight(&limiter), 2);
+    }
+
+    #[tokio::test]
+    async fn interactive_goes_before_bulk() {
+        let limiter = Limiter::new(RateLimit::new().max_in_flight(1));
+        let held = limiter.acquire(Priority::Interactive).await;
+        let mut bulk = Box::pin(limiter.acquire(Priority::Bulk));
+        assert!((&mut bulk).now_or_never().is_none());
+        let mut interactive = Box::pin(limiter.acquire(Priority::Interactive));
+        assert!((&mut interactive).now_or_never().is_none());
+        assert_eq!(waiting(&limiter, Priority::Bulk), 1);
+        assert_eq!(waiting(&limiter, Priority::Interactive), 1);
+
+        drop(held);
+        assert!((&mut bulk).now_or_never().is_none());
+        let permit = interactive.now_or_never().expect("interactive first");
+        drop(permit);
+        bulk.now_or_never().expect("bulk once interactive is done");
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiter_gives_back_its_slot() {
+        let limiter = Limiter::new(RateLimit::new().max_in_flight(1));
+        let held = limiter.acquire(Priority::Interactive).await;
+        let mut cancelled = Box::pin(limiter.acquire(Priority::Interactive));
+        assert!((&mut cancelled).now_or_never().is_none());
+        drop(cancelled);
+
+        drop(held);
+        assert_eq!(in_flight(&limiter), 0);
+        let _permit = limiter
+            .acquire(Priority::Interactive)
+            .now_or_never()
+            .expect("the slot is free");
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiter_gives_back_its_token() {
+        let limiter = Limiter::new(RateLimit::new().per_second(20.0, 1));
+        limiter.acquire(Priority::Interactive).await;
+        let mut cancelled = Box::pin(limiter.acquire(Priority::Interactive));
+        assert!((&mut cancelled).now_or_never().is_none());
+        drop(cancelled);
+
+        // the timer hands the token to the dropped waiter, which gives it back
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(in_flight(&limiter), 0);
+        limiter
+            .acquire(Priority::Interactive)
+            .now_or_never()
+            .expect("the token is back");
+    }
+}
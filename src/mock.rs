@@ -0,0 +1,153 @@
+use std::any::TypeId;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+
+use crate::api::Aria2Api;
+use crate::call::Call;
+use crate::error::{Error, RpcError};
+use crate::{Notification, Result};
+
+/// A call received by a [`MockClient`], params are recorded without the token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedCall {
+    pub method: String,
+    pub params: Option<serde_json::Value>,
+}
+
+type MockResponse = std::result::Result<serde_json::Value, RpcError>;
+
+#[derive(Default)]
+struct MockState {
+    by_type: HashMap<TypeId, VecDeque<MockResponse>>,
+    by_method: HashMap<String, VecDeque<MockResponse>>,
+    calls: Vec<RecordedCall>,
+}
+
+/// [`Aria2Api`] serving scripted responses, for unit testing code built on
+/// top of [`Client`](crate::Client) without a running aria2.
+///
+/// Responses are queued per `Call` type, or per method name, and each is
+/// served once in order. A call without a scripted response fails with an
+/// [`RpcError`] of code 1.
+#[derive(Clone)]
+pub struct MockClient {
+    state: Arc<Mutex<MockState>>,
+    notification_tx: mpsc::UnboundedSender<Notification>,
+}
+
+impl MockClient {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<Notification>) {
+        let (notification_tx, notification_rx) = mpsc::unbounded_channel();
+        let client = Self {
+            state: Default::default(),
+            notification_tx,
+        };
+        (client, notification_rx)
+    }
+
+    /// queue the result of the next call of type `C`, encoded as aria2 would.
+    pub fn reply<C: Call + 'static>(&self, result: impl serde::Serialize) -> &Self {
+        let value = serde_json::to_value(result).expect("mock reply must be serializable");
+        self.push(TypeId::of::<C>(), Ok(value))
+    }
+
+    /// queue an error for the next call of type `C`.
+    pub fn fail<C: Call + 'static>(&self, error: RpcError) -> &Self {
+        self.push(TypeId::of::<C>(), Err(error))
+    }
+
+    /// queue the result of the next call of `method`, used when no response
+    /// is queued for the type of the call.
+    pub fn reply_method(&self, method: impl Into<String>, result: impl serde::Serialize) -> &Self {
+        let value = serde_json::to_value(result).expect("mock reply must be serializable");
+        self.state
+            .lock()
+            .unwrap()
+            .by_method
+            .entry(method.into())
+            .or_default()
+            .push_back(Ok(value));
+        self
+    }
+
+    pub fn fail_method(&self, method: impl Into<String>, error: RpcError) -> &Self {
+        self.state
+            .lock()
+            .unwrap()
+            .by_method
+            .entry(method.into())
+            .or_default()
+            .push_back(Err(error));
+        self
+    }
+
+    fn push(&self, key: TypeId, response: MockResponse) -> &Self {
+        self.state
+            .lock()
+            .unwrap()
+            .by_type
+            .entry(key)
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// deliver a notification to the receiver returned by [`MockClient::new`].
+    pub fn notify(&self, notification: Notification) {
+        let _ = self.notification_tx.send(notification);
+    }
+
+    /// every call received so far, in order.
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// calls received for `method`, in order.
+    pub fn calls_to(&self, method: &str) -> Vec<RecordedCall> {
+        self.state
+            .lock()
+            .unwrap()
+            .calls
+            .iter()
+            .filter(|c| c.method == method)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Aria2Api for MockClient {
+    async fn call<C>(&self, call: C) -> Result<C::Response>
+    where
+        C: Call + Send + 'static,
+    {
+        let method = call.method().into_owned();
+        let params = match call.to_params(None) {
+            Some(params) => Some(serde_json::to_value(params).map_err(Error::Encode)?),
+            None => None,
+        };
+
+        let response = {
+            let mut state = self.state.lock().unwrap();
+            state.calls.push(RecordedCall {
+                method: method.clone(),
+                params,
+            });
+            let by_type = state
+                .by_type
+                .get_mut(&TypeId::of::<C>())
+                .and_then(VecDeque::pop_front);
+            by_type.or_else(|| state.by_method.get_mut(&method)?.pop_front())
+        };
+
+        match response {
+            Some(Ok(value)) => serde_json::from_value(value).map_err(Error::Decode),
+            Some(Err(e)) => Err(Error::Rpc(e)),
+            None => Err(Error::Rpc(RpcError {
+                code: 1,
+                message: format!("no mock response for {method}"),
+            })),
+        }
+    }
+}
@@ -1,12 +1,98 @@
-#[derive(Debug, serde::Serialize, Default)]
-#[serde(rename_all = "kebab-case")]
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_with::{DisplayFromStr, OneOrMany};
+
+use crate::secret::is_secret_option;
+use crate::{Gid, Secret};
+
+/// Options of aria2, as sent in calls or read from its
+/// [configuration file](crate::conf). Values are strings on the wire.
+#[serde_with::serde_as]
 #[serde_with::skip_serializing_none]
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
 pub struct Aria2Options {
     // == basic
     pub dir: Option<String>,
+    /// assign the gid of a new download instead of letting aria2 generate one
+    pub gid: Option<Gid>,
     // == http_ftp_sftp
     pub out: Option<String>,
     // == http specific
+    /// `Name: value` lines, the option can be given several times
+    #[serde_as(as = "Option<OneOrMany<_>>")]
+    pub header: Option<Vec<String>>,
     pub referer: Option<String>,
     pub user_agent: Option<String>,
+    // == bittorrent specific
+    /// `index=path` of the files to write under another name, the option can
+    /// be given several times
+    #[serde_as(as = "Option<OneOrMany<_>>")]
+    pub index_out: Option<Vec<String>>,
+    // == rpc, global only and fixed at startup but for the secret
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub enable_rpc: Option<bool>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub rpc_listen_port: Option<u16>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub rpc_secure: Option<bool>,
+    pub rpc_certificate: Option<String>,
+    /// global only, changed by `Client::rotate_secret`
+    pub rpc_secret: Option<Secret>,
+    /// options without a field above, such as `split` or `select-file`, kept
+    /// as given
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, String>,
+}
+
+/// the secret options in `extra`, such as `ftp-passwd`, are redacted
+impl fmt::Debug for Aria2Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let extra: BTreeMap<&str, &str> = self
+            .extra
+            .iter()
+            .map(|(key, value)| match is_secret_option(key) {
+                true => (key.as_str(), "<redacted>"),
+                false => (key.as_str(), value.as_str()),
+            })
+            .collect();
+        f.debug_struct("Aria2Options")
+            .field("dir", &self.dir)
+            .field("gid", &self.gid)
+            .field("out", &self.out)
+            .field("header", &self.header)
+            .field("referer", &self.referer)
+            .field("user_agent", &self.user_agent)
+            .field("index_out", &self.index_out)
+            .field("enable_rpc", &self.enable_rpc)
+            .field("rpc_listen_port", &self.rpc_listen_port)
+            .field("rpc_secure", &self.rpc_secure)
+            .field("rpc_certificate", &self.rpc_certificate)
+            .field("rpc_secret", &self.rpc_secret)
+            .field("extra", &extra)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_not_debugged() {
+        let mut options = Aria2Options {
+            dir: Some("/d".to_string()),
+            rpc_secret: Some(Secret::new("RPCSECRET")),
+            ..Default::default()
+        };
+        options.extra.insert("ftp-passwd".to_string(), "FTPPASSWD".to_string());
+        options.extra.insert("split".to_string(), "4".to_string());
+        let debugged = format!("{options:?}");
+        for secret in ["RPCSECRET", "FTPPASSWD"] {
+            assert!(!debugged.contains(secret), "{secret} in {debugged}");
+        }
+        assert!(debugged.contains("\"/d\""));
+        assert!(debugged.contains("\"split\": \"4\""));
+    }
 }
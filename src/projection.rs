@@ -0,0 +1,227 @@
+//! Typed field projection for `aria2.tellStatus` and friends.
+//!
+//! The keys to request are taken from the fields of a struct, and the reply is
+//! decoded straight into it:
+//!
+//! ```ignore
+//! use aria2_rs_yet::projection::{Projectable, Projection};
+//!
+//! #[derive(Debug, Projection)]
+//! struct Progress {
+//!     gid: Gid,
+//!     total_length: u64,
+//!     completed_length: u64,
+//!     // only present once the download started
+//!     bitfield: Option<String>,
+//! }
+//!
+//! let progress: Progress = client.call(TellStatus::new(gid).project()).await?;
+//! let active: Vec<Progress> = client.call(TellActive::new().project()).await?;
+//! ```
+//!
+//! Every field must be named after one of the markers in [`keys`], anything
+//! else does not compile. A field has the type of its key, or an `Option` of it
+//! for keys aria2 only sends in some states.
+
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use serde::ser::SerializeSeq;
+
+pub use aria2_rs_yet_derive::Projection;
+
+use crate::call::{Call, TellActive, TellStatus, TellStatusField, TellStopped, TellWaiting};
+use crate::limit::Priority;
+use crate::Gid;
+
+/// A struct whose fields are a subset of the keys of a `tellStatus` reply,
+/// usually derived.
+pub trait Projection: serde::de::DeserializeOwned {
+    const KEYS: &'static [TellStatusField];
+}
+
+/// A key of a `tellStatus` reply, see [`keys`].
+pub trait Key {
+    const FIELD: TellStatusField;
+    const NAME: &'static str;
+    type Value;
+
+    fn decode(value: serde_json::Value) -> Result<Self::Value, serde_json::Error>;
+}
+
+/// Types a field of key `V` can be declared as, `V` itself or `Option<V>`.
+pub trait Project<V>: Sized {
+    fn project(value: Option<V>) -> Option<Self>;
+}
+
+impl<V> Project<V> for V {
+    fn project(value: Option<V>) -> Option<Self> {
+        value
+    }
+}
+
+impl<V> Project<V> for Option<V> {
+    fn project(value: Option<V>) -> Option<Self> {
+        Some(value)
+    }
+}
+
+#[doc(hidden)]
+pub fn take<K, T, E>(map: &mut serde_json::Map<String, serde_json::Value>) -> Result<T, E>
+where
+    K: Key,
+    T: Project<K::Value>,
+    E: serde::de::Error,
+{
+    let value = map
+        .remove(K::NAME)
+        .map(K::decode)
+        .transpose()
+        .map_err(E::custom)?;
+    T::project(value).ok_or_else(|| E::missing_field(K::NAME))
+}
+
+/// One marker per key of a `tellStatus` reply, named as the field of a
+/// [`Projection`] requesting it.
+#[allow(non_camel_case_types)]
+pub mod keys {
+    use serde::Deserialize;
+    use serde_with::{As, DisplayFromStr};
+
+    use super::Key;
+    use crate::call::{TaskStatus, TellStatusField, TellStatusReplyFile};
+    use crate::{DownloadErrorCode, Gid};
+
+    macro_rules! key {
+        ($marker: ident, $field: ident, $name: literal, $value: ty) => {
+            key!($marker, $field, $name, $value, |v| <$value>::deserialize(v));
+        };
+        ($marker: ident, $field: ident, $name: literal, $value: ty, from_str) => {
+            key!($marker, $field, $name, $value, |v| As::<DisplayFromStr>::deserialize(v));
+        };
+        ($marker: ident, $field: ident, $name: literal, $value: ty, $decode: expr) => {
+            #[doc = concat!("`", $name, "`")]
+            pub struct $marker;
+            impl Key for $marker {
+                const FIELD: TellStatusField = TellStatusField::$field;
+                const NAME: &'static str = $name;
+                type Value = $value;
+
+                fn decode(value: serde_json::Value) -> Result<$value, serde_json::Error> {
+                    let decode: fn(serde_json::Value) -> Result<$value, serde_json::Error> =
+                        $decode;
+                    decode(value)
+                }
+            }
+        };
+    }
+
+    key!(gid, Gid, "gid", Gid);
+    key!(status, Status, "status", TaskStatus);
+    key!(total_length, TotalLength, "totalLength", u64, from_str);
+    key!(completed_length, CompletedLength, "completedLength", u64, from_str);
+    key!(upload_length, UploadedLength, "uploadLength", u64, from_str);
+    key!(bitfield, BitField, "bitfield", String);
+    key!(download_speed, DownloadSpeed, "downloadSpeed", u64, from_str);
+    key!(upload_speed, UploadSpeed, "uploadSpeed", u64, from_str);
+    key!(info_hash, InfoHash, "infoHash", String);
+    key!(num_seeders, NumSeeders, "numSeeders", u64, from_str);
+    key!(seeder, Seeder, "seeder", bool, from_str);
+    key!(piece_length, PieceLength, "pieceLength", u64, from_str);
+    key!(num_pieces, NumPieces, "numPieces", u64, from_str);
+    key!(connections, Connections, "connections", u64, from_str);
+    key!(error_code, ErrorCode, "errorCode", DownloadErrorCode);
+    key!(error_message, ErrorMessage, "errorMessage", String);
+    key!(followed_by, FollowedBy, "followedBy", Vec<Gid>);
+    key!(following, Following, "following", Gid);
+    key!(belongs_to, BelongsTo, "belongsTo", Gid);
+    key!(dir, Dir, "dir", String);
+    key!(files, Files, "files", Vec<TellStatusReplyFile>);
+    key!(verified_length, VerifiedLength, "verifiedLength", u64, from_str);
+    key!(
+        verify_integrity_pending,
+        VeriyIntegrityPending,
+        "verifyIntegrityPending",
+        bool,
+        from_str
+    );
+}
+
+/// Calls accepting a set of keys, which can be replaced by those of a [`Projection`].
+pub trait Projectable: Call + Sized {
+    type Output<P: Projection>: serde::de::DeserializeOwned;
+
+    fn with_fields(self, fields: &[TellStatusField]) -> Self;
+
+    fn project<P: Projection>(self) -> Projected<Self, P> {
+        Projected {
+            call: self.with_fields(P::KEYS),
+            _projection: PhantomData,
+        }
+    }
+}
+
+impl Projectable for TellStatus {
+    type Output<P: Projection> = P;
+
+    fn with_fields(self, fields: &[TellStatusField]) -> Self {
+        self.fields(Some(fields.iter().copied()))
+    }
+}
+
+impl Projectable for TellActive {
+    type Output<P: Projection> = Vec<P>;
+
+    fn with_fields(self, fields: &[TellStatusField]) -> Self {
+        self.fields(Some(fields.iter().copied()))
+    }
+}
+
+impl Projectable for TellWaiting {
+    type Output<P: Projection> = Vec<P>;
+
+    fn with_fields(self, fields: &[TellStatusField]) -> Self {
+        self.fields(Some(fields.iter().copied()))
+    }
+}
+
+impl Projectable for TellStopped {
+    type Output<P: Projection> = Vec<P>;
+
+    fn with_fields(self, fields: &[TellStatusField]) -> Self {
+        self.fields(Some(fields.iter().copied()))
+    }
+}
+
+/// A call whose reply is decoded into the projection `P`.
+#[derive(Debug)]
+pub struct Projected<C, P> {
+    call: C,
+    _projection: PhantomData<fn() -> P>,
+}
+
+impl<C, P> Call for Projected<C, P>
+where
+    C: Projectable,
+    P: Projection,
+{
+    type Response = C::Output<P>;
+    const IDEMPOTENT: bool = C::IDEMPOTENT;
+    const PRIORITY: Priority = C::PRIORITY;
+
+    fn method(&self) -> Cow<'static, str> {
+        self.call.method()
+    }
+
+    fn gid(&self) -> Option<&Gid> {
+        self.call.gid()
+    }
+
+    fn needs_token(&self) -> bool {
+        self.call.needs_token()
+    }
+
+    fn serialize_params<S: SerializeSeq>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        self.call.serialize_params(serializer)
+    }
+}
@@ -0,0 +1,619 @@
+//! The json-rpc side of a connection, free of any I/O: request ids, requests
+//! waiting for their response, and decoding of responses and notifications.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::oneshot;
+
+use crate::call::{
+    Call, GetSessionInfo, SessionInfoReply, TaskStatus, TellActive, TellStatusField,
+    TellStatusReply, TellStopped, TellWaiting,
+};
+use crate::jsonrpc;
+use crate::Gid;
+
+#[derive(Debug, Clone)]
+pub enum Notification {
+    DownloadStart(Gid),
+    DownloadPause(Gid),
+    DownloadStop(Gid),
+    DownloadComplete(Gid),
+    DownloadError(Gid),
+    BtDownloadComplete(Gid),
+    /// Synthesized after a reconnect for a transition that happened while the
+    /// connection was down, see [`Notification::is_reconciled`].
+    Reconciled(Box<Notification>),
+    /// The session id changed across a reconnect, aria2 was restarted behind
+    /// the same url. Carries the new session id.
+    ///
+    /// Gids seen before may no longer exist, or may have been restored from a
+    /// session file; the reconciled notifications following this one only
+    /// cover tasks that are still known to the new instance.
+    ServerRestarted(String),
+}
+
+impl Notification {
+    /// the notification of `method`, None for a method this crate does not know.
+    pub fn new(method: &str, gid: Gid) -> Option<Self> {
+        match method {
+            "aria2.onDownloadStart" => Some(Self::DownloadStart(gid)),
+            "aria2.onDownloadPause" => Some(Self::DownloadPause(gid)),
+            "aria2.onDownloadStop" => Some(Self::DownloadStop(gid)),
+            "aria2.onDownloadComplete" => Some(Self::DownloadComplete(gid)),
+            "aria2.onDownloadError" => Some(Self::DownloadError(gid)),
+            "aria2.onBtDownloadComplete" => Some(Self::BtDownloadComplete(gid)),
+            _ => None,
+        }
+    }
+
+    pub fn gid(&self) -> Option<&Gid> {
+        match self {
+            Self::DownloadStart(gid)
+            | Self::DownloadPause(gid)
+            | Self::DownloadStop(gid)
+            | Self::DownloadComplete(gid)
+            | Self::DownloadError(gid)
+            | Self::BtDownloadComplete(gid) => Some(gid),
+            Self::Reconciled(inner) => inner.gid(),
+            Self::ServerRestarted(_) => None,
+        }
+    }
+
+    /// true if the notification was not sent by aria2 but derived from the
+    /// queue snapshot taken after a reconnect.
+    pub fn is_reconciled(&self) -> bool {
+        matches!(self, Self::Reconciled(_))
+    }
+
+    /// status of the task right after this notification, if it implies one.
+    fn status(&self) -> Option<TaskStatus> {
+        match self {
+            Self::DownloadStart(_) => Some(TaskStatus::Active),
+            Self::DownloadPause(_) => Some(TaskStatus::Paused),
+            Self::DownloadStop(_) => Some(TaskStatus::Removed),
+            Self::DownloadComplete(_) => Some(TaskStatus::Complete),
+            Self::DownloadError(_) => Some(TaskStatus::Error),
+            Self::BtDownloadComplete(_) | Self::ServerRestarted(_) => None,
+            Self::Reconciled(inner) => inner.status(),
+        }
+    }
+
+    /// notification aria2 would have sent when a task moved into `status`.
+    fn from_status(status: TaskStatus, gid: Gid) -> Option<Self> {
+        match status {
+            TaskStatus::Active => Some(Self::DownloadStart(gid)),
+            TaskStatus::Paused => Some(Self::DownloadPause(gid)),
+            TaskStatus::Removed => Some(Self::DownloadStop(gid)),
+            TaskStatus::Complete => Some(Self::DownloadComplete(gid)),
+            TaskStatus::Error => Some(Self::DownloadError(gid)),
+            TaskStatus::Waiting => None,
+        }
+    }
+}
+
+/// max number of waiting/stopped tasks fetched for a snapshot, and of stopped
+/// tasks tracked between two, same as the default of aria2's
+/// `--max-download-result`.
+const SNAPSHOT_LIMIT: i32 = 1000;
+
+/// last known status of every task, used to find transitions missed while offline.
+#[derive(Default)]
+struct Tracker {
+    /// None until a first snapshot is reconciled, the baseline later ones
+    /// are compared to
+    session_id: Option<String>,
+    tasks: HashMap<Gid, Observed>,
+    /// tasks that reached a terminal status, oldest first, with when
+    finished: VecDeque<(Gid, u64)>,
+    /// notifications observed so far, orders them against snapshot requests
+    seen: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Observed {
+    status: TaskStatus,
+    /// value of [`Tracker::seen`] when the status was learned
+    seen: u64,
+}
+
+/// the task will not change status by itself anymore.
+fn is_terminal(status: TaskStatus) -> bool {
+    matches!(
+        status,
+        TaskStatus::Complete | TaskStatus::Error | TaskStatus::Removed
+    )
+}
+
+impl Tracker {
+    fn observe(&mut self, notification: &Notification) {
+        if let (Some(gid), Some(status)) = (notification.gid(), notification.status()) {
+            self.seen += 1;
+            let observed = Observed {
+                status,
+                seen: self.seen,
+            };
+            self.tasks.insert(gid.clone(), observed);
+            if is_terminal(status) {
+                self.finished.push_back((gid.clone(), self.seen));
+                self.forget_finished();
+            }
+        }
+    }
+
+    /// forget the oldest of the stopped tasks beyond [`SNAPSHOT_LIMIT`], as
+    /// aria2 does of their results.
+    fn forget_finished(&mut self) {
+        while self.finished.len() > SNAPSHOT_LIMIT as usize {
+            let Some((gid, seen)) = self.finished.pop_front() else {
+                break;
+            };
+            // unless it changed since
+            if self.tasks.get(&gid).is_some_and(|known| known.seen == seen) {
+                self.tasks.remove(&gid);
+            }
+        }
+    }
+
+    /// replace the known state with `snapshot`, returning the notifications
+    /// for every task whose status changed since the last known state.
+    ///
+    /// Tasks missing from the snapshot are forgotten, so gids dropped by an
+    /// aria2 restart are invalidated while those restored from a session file
+    /// are carried over. Tasks notified after the snapshot was requested keep
+    /// their newer status. The first snapshot is the baseline, nothing is
+    /// missed before it.
+    fn reconcile(&mut self, snapshot: Snapshot) -> Vec<Notification> {
+        let mut missed = Vec::new();
+        let baseline = self.session_id.is_none();
+        let session_id = snapshot.session.session_id;
+        if self.session_id.as_ref().is_some_and(|prev| *prev != session_id) {
+            tracing::warn!("aria2 restarted, new session id: {session_id}");
+            missed.push(Notification::ServerRestarted(session_id.clone()));
+        }
+        self.session_id = Some(session_id);
+
+        // the queues are listed one call after the other, a task moving
+        // between them meanwhile is listed twice
+        let mut order = Vec::with_capacity(snapshot.tasks.len());
+        let mut listed = HashMap::with_capacity(snapshot.tasks.len());
+        for (gid, status) in snapshot.tasks {
+            match listed.get(&gid) {
+                None => order.push(gid.clone()),
+                Some(&prev) if is_terminal(prev) || !is_terminal(status) => continue,
+                Some(_) => {}
+            }
+            listed.insert(gid, status);
+        }
+
+        let mut tasks = HashMap::with_capacity(order.len());
+        for gid in order {
+            let status = listed[&gid];
+            let known = self.tasks.get(&gid);
+            if known.is_some_and(|known| known.seen > snapshot.requested_at) {
+                continue;
+            }
+            if !baseline && known.map(|known| known.status) != Some(status) {
+                if let Some(n) = Notification::from_status(status, gid.clone()) {
+                    missed.push(Notification::Reconciled(Box::new(n)));
+                }
+            }
+            let observed = Observed {
+                status,
+                seen: snapshot.requested_at,
+            };
+            tasks.insert(gid, observed);
+        }
+        // newer than the snapshot
+        tasks.extend(
+            self.tasks
+                .drain()
+                .filter(|(_, observed)| observed.seen > snapshot.requested_at),
+        );
+        self.tasks = tasks;
+
+        let mut finished: Vec<_> = self
+            .tasks
+            .iter()
+            .filter(|(_, known)| is_terminal(known.status))
+            .map(|(gid, known)| (gid.clone(), known.seen))
+            .collect();
+        finished.sort_by_key(|(_, seen)| *seen);
+        self.finished = finished.into();
+        self.forget_finished();
+        missed
+    }
+}
+
+pub(crate) struct Snapshot {
+    /// [`Tracker::seen`] when the snapshot was requested
+    requested_at: u64,
+    session: SessionInfoReply,
+    tasks: Vec<(Gid, TaskStatus)>,
+}
+
+/// replies to the requests of a snapshot, still to arrive.
+pub(crate) struct PendingSnapshot {
+    requested_at: u64,
+    replies: Vec<(Cow<'static, str>, oneshot::Receiver<RPCReponse>)>,
+}
+
+impl PendingSnapshot {
+    /// wait for every reply, `None` if one failed.
+    pub(crate) async fn collect(self) -> Option<Snapshot> {
+        let mut session = None;
+        let mut tasks = Vec::new();
+        for (method, rx) in self.replies {
+            let value = match rx.await {
+                Ok(RPCReponse::Success(value)) => value,
+                Ok(RPCReponse::Error(e)) => {
+                    tracing::error!(method = %method, "snapshot error: {}", e.message);
+                    return None;
+                }
+                Err(_) => return None,
+            };
+            if session.is_none() {
+                match serde_json::from_value::<SessionInfoReply>(value) {
+                    Ok(info) => session = Some(info),
+                    Err(e) => {
+                        tracing::error!(method = %method, "session info decode error: {e}");
+                        return None;
+                    }
+                }
+                continue;
+            }
+            match serde_json::from_value::<Vec<TellStatusReply>>(value) {
+                Ok(replies) => tasks.extend(
+                    replies
+                        .into_iter()
+                        .filter_map(|reply| Some((reply.gid?, reply.status?))),
+                ),
+                Err(e) => {
+                    tracing::error!(method = %method, "snapshot decode error: {e}");
+                    return None;
+                }
+            }
+        }
+        Some(Snapshot {
+            requested_at: self.requested_at,
+            session: session?,
+            tasks,
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct NotificationParam {
+    gid: Gid,
+}
+
+pub(crate) struct RPCRequest {
+    pub(crate) id: i64,
+    pub(crate) params: Option<serde_json::Value>,
+    pub(crate) method: String,
+    pub(crate) handler: oneshot::Sender<RPCReponse>,
+}
+
+pub(crate) enum RPCReponse {
+    Success(serde_json::Value),
+    Error(jsonrpc::Error),
+}
+
+/// State of a connection across reconnects. The I/O layer hands it every
+/// frame received and sends the frames it returns.
+pub(crate) struct Protocol {
+    request_id: Arc<AtomicI64>,
+    pending: HashMap<i64, oneshot::Sender<RPCReponse>>,
+    tracker: Tracker,
+}
+
+impl Protocol {
+    /// `request_id` is shared with the client, which numbers its own requests.
+    pub(crate) fn new(request_id: Arc<AtomicI64>) -> Self {
+        Self {
+            request_id,
+            pending: HashMap::new(),
+            tracker: Tracker::default(),
+        }
+    }
+
+    /// the frame of `request`, whose response goes to its handler.
+    pub(crate) fn request(&mut self, request: RPCRequest) -> serde_json::Result<String> {
+        let text = encode(request.id, &request.method, request.params)?;
+        self.pending.insert(request.id, request.handler);
+        Ok(text)
+    }
+
+    /// the frames asking for the session info and the current state of all
+    /// queues, with the replies to collect into a [`Snapshot`].
+    pub(crate) fn snapshot(
+        &mut self,
+        token: Option<&str>,
+    ) -> serde_json::Result<(Vec<String>, PendingSnapshot)> {
+        let fields = [TellStatusField::Gid, TellStatusField::Status];
+        let requests = [
+            (
+                GetSessionInfo.method(),
+                serde_json::to_value(GetSessionInfo.to_params(token)),
+            ),
+            (
+                TellActive::new().method(),
+                serde_json::to_value(TellActive::new_with_fields(fields).to_params(token)),
+            ),
+            (
+                TellWaiting::new(0, 0).method(),
+                serde_json::to_value(
+                    TellWaiting::new_with_fields(0, SNAPSHOT_LIMIT, fields).to_params(token),
+                ),
+            ),
+            (
+                TellStopped::new(0, 0).method(),
+                // newest first, those that stopped while disconnected
+                serde_json::to_value(
+                    TellStopped::new_with_fields(-1, SNAPSHOT_LIMIT, fields).to_params(token),
+                ),
+            ),
+        ];
+
+        let mut frames = Vec::with_capacity(requests.len());
+        let mut replies = Vec::with_capacity(requests.len());
+        for (method, params) in requests {
+            let params = params?;
+            let id = self.request_id.fetch_add(1, Ordering::Relaxed) + 1;
+            frames.push(encode(id, &method, Some(params))?);
+            let (tx, rx) = oneshot::channel();
+            self.pending.insert(id, tx);
+            replies.push((method, rx));
+        }
+        let pending = PendingSnapshot {
+            requested_at: self.tracker.seen,
+            replies,
+        };
+        Ok((frames, pending))
+    }
+
+    /// handle a frame received, returning the notifications it carried.
+    pub(crate) fn receive(&mut self, text: &str) -> Vec<Notification> {
+        let Ok(resp) = serde_json::from_str::<
+            jsonrpc::Response<i64, serde_json::Value, Vec<NotificationParam>>,
+        >(text) else {
+            return Vec::new();
+        };
+        match resp {
+            jsonrpc::Response::Err { id, error } => {
+                if let Some(tx) = self.pending.remove(&id) {
+                    let _ = tx.send(RPCReponse::Error(error));
+                }
+                Vec::new()
+            }
+            jsonrpc::Response::Resp { id, result } => {
+                if let Some(tx) = self.pending.remove(&id) {
+                    let _ = tx.send(RPCReponse::Success(result));
+                }
+                Vec::new()
+            }
+            jsonrpc::Response::Notification { method, params } => {
+                let notifications: Vec<_> = params
+                    .into_iter()
+                    .filter_map(|param| Notification::new(&method, param.gid))
+                    .collect();
+                if notifications.is_empty() {
+                    tracing::debug!(method = %method, "unknown notification skipped");
+                }
+                notifications.iter().for_each(|n| self.tracker.observe(n));
+                notifications
+            }
+        }
+    }
+
+    /// the notifications missed before `snapshot` was taken.
+    pub(crate) fn reconcile(&mut self, snapshot: Snapshot) -> Vec<Notification> {
+        self.tracker.reconcile(snapshot)
+    }
+
+    /// no request is waiting for its response.
+    pub(crate) fn is_idle(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// the connection was lost, requests waiting for a response fail.
+    pub(crate) fn disconnected(&mut self) {
+        self.pending.clear();
+    }
+}
+
+fn encode(id: i64, method: &str, params: Option<serde_json::Value>) -> serde_json::Result<String> {
+    let rpc_req = jsonrpc::Request {
+        id: Some(id),
+        jsonrpc: "2.0",
+        method,
+        params,
+    };
+    serde_json::to_string(&rpc_req)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::FutureExt;
+
+    use super::*;
+
+    fn protocol() -> Protocol {
+        Protocol::new(Arc::new(AtomicI64::new(0)))
+    }
+
+    fn notification(method: &str, gid: &str) -> String {
+        serde_json::json!({"jsonrpc": "2.0", "method": method, "params": [{"gid": gid}]})
+            .to_string()
+    }
+
+    const GID: &str = "2089b05ecca3d829";
+
+    fn gid() -> Gid {
+        GID.parse().unwrap()
+    }
+
+    fn tasks(status: &str, gids: &[&str]) -> serde_json::Value {
+        gids.iter()
+            .map(|gid| serde_json::json!({"gid": gid, "status": status}))
+            .collect()
+    }
+
+    /// request a snapshot, let `before` run, then answer with the queues.
+    fn snapshot(
+        protocol: &mut Protocol,
+        before: impl FnOnce(&mut Protocol),
+        [active, waiting, stopped]: [serde_json::Value; 3],
+    ) -> Snapshot {
+        let (frames, pending) = protocol.snapshot(None).unwrap();
+        before(protocol);
+        let results = [serde_json::json!({"sessionId": "s1"}), active, waiting, stopped];
+        for (frame, result) in frames.iter().zip(results) {
+            let id = serde_json::from_str::<serde_json::Value>(frame).unwrap()["id"].clone();
+            let reply = serde_json::json!({"jsonrpc": "2.0", "id": id, "result": result});
+            protocol.receive(&reply.to_string());
+        }
+        pending.collect().now_or_never().unwrap().unwrap()
+    }
+
+    fn empty() -> [serde_json::Value; 3] {
+        [tasks("active", &[]), tasks("waiting", &[]), tasks("complete", &[])]
+    }
+
+    /// a protocol with the baseline taken on connecting, all queues empty.
+    fn connected() -> Protocol {
+        let mut protocol = protocol();
+        let snapshot = snapshot(&mut protocol, |_| {}, empty());
+        assert!(protocol.reconcile(snapshot).is_empty());
+        protocol
+    }
+
+    fn status(protocol: &Protocol) -> Option<TaskStatus> {
+        protocol.tracker.tasks.get(&gid()).map(|known| known.status)
+    }
+
+    #[test]
+    fn task_listed_twice_is_reconciled_once() {
+        let mut protocol = connected();
+        protocol.receive(&notification("aria2.onDownloadStart", GID));
+        protocol.disconnected();
+        // completed between tellActive and tellStopped
+        let snapshot = snapshot(
+            &mut protocol,
+            |_| {},
+            [tasks("active", &[GID]), tasks("waiting", &[]), tasks("complete", &[GID])],
+        );
+        let missed = protocol.reconcile(snapshot);
+        assert!(matches!(
+            missed.as_slice(),
+            [Notification::Reconciled(n)] if matches!(**n, Notification::DownloadComplete(_))
+        ));
+        assert_eq!(status(&protocol), Some(TaskStatus::Complete));
+    }
+
+    #[test]
+    fn notification_after_request_is_kept() {
+        let mut protocol = connected();
+        protocol.receive(&notification("aria2.onDownloadStart", GID));
+        protocol.disconnected();
+        let snapshot = snapshot(
+            &mut protocol,
+            |protocol| {
+                protocol.receive(&notification("aria2.onDownloadPause", GID));
+            },
+            [tasks("active", &[GID]), tasks("waiting", &[]), tasks("complete", &[])],
+        );
+        assert!(protocol.reconcile(snapshot).is_empty());
+        assert_eq!(status(&protocol), Some(TaskStatus::Paused));
+    }
+
+    #[test]
+    fn task_added_after_request_is_kept() {
+        let mut protocol = protocol();
+        let snapshot = snapshot(
+            &mut protocol,
+            |protocol| {
+                protocol.receive(&notification("aria2.onDownloadStart", GID));
+            },
+            empty(),
+        );
+        assert!(protocol.reconcile(snapshot).is_empty());
+        assert_eq!(status(&protocol), Some(TaskStatus::Active));
+    }
+
+    #[test]
+    fn missed_transition_is_reconciled() {
+        let mut protocol = connected();
+        protocol.receive(&notification("aria2.onDownloadStart", GID));
+        protocol.disconnected();
+        let snapshot = snapshot(
+            &mut protocol,
+            |_| {},
+            [tasks("active", &[]), tasks("paused", &[GID]), tasks("complete", &[])],
+        );
+        let missed = protocol.reconcile(snapshot);
+        assert!(matches!(
+            missed.as_slice(),
+            [Notification::Reconciled(n)] if matches!(**n, Notification::DownloadPause(_))
+        ));
+    }
+
+    #[test]
+    fn no_baseline_reconciles_nothing() {
+        let mut protocol = protocol();
+        protocol.receive(&notification("aria2.onDownloadStart", GID));
+        // the snapshot taken on connecting failed
+        let _ = protocol.snapshot(None).unwrap();
+        protocol.disconnected();
+        let snapshot = snapshot(
+            &mut protocol,
+            |_| {},
+            [tasks("active", &[]), tasks("waiting", &[]), tasks("complete", &[GID])],
+        );
+        assert!(protocol.reconcile(snapshot).is_empty());
+        assert_eq!(status(&protocol), Some(TaskStatus::Complete));
+    }
+
+    #[test]
+    fn stopped_are_fetched_newest_first() {
+        let (frames, _) = protocol().snapshot(None).unwrap();
+        let request: serde_json::Value = serde_json::from_str(&frames[3]).unwrap();
+        assert_eq!(request["method"], "aria2.tellStopped");
+        assert_eq!(request["params"][0], -1);
+        assert_eq!(request["params"][1], SNAPSHOT_LIMIT);
+    }
+
+    #[test]
+    fn stopped_tasks_are_capped() {
+        let mut protocol = connected();
+        let limit = SNAPSHOT_LIMIT as u64;
+        let gids: Vec<String> = (0..limit + 10).map(|i| format!("{i:016x}")).collect();
+        for gid in &gids {
+            protocol.receive(&notification("aria2.onDownloadStart", gid));
+            protocol.receive(&notification("aria2.onDownloadComplete", gid));
+        }
+        let tasks = &protocol.tracker.tasks;
+        assert_eq!(tasks.len(), SNAPSHOT_LIMIT as usize);
+        assert!(!tasks.contains_key(&gids[9].parse().unwrap()));
+        assert!(tasks.contains_key(&gids[10].parse().unwrap()));
+
+        // active ones are kept
+        let active = "ffffffffffffffff";
+        protocol.receive(&notification("aria2.onDownloadStart", active));
+        assert!(protocol.tracker.tasks.contains_key(&active.parse().unwrap()));
+    }
+
+    #[test]
+    fn unknown_notification_is_skipped() {
+        let mut protocol = protocol();
+        let frame = notification("aria2.onSomethingNew", "2089b05ecca3d829");
+        assert!(protocol.receive(&frame).is_empty());
+        let frame = notification("aria2.onDownloadStart", "2089b05ecca3d829");
+        assert!(matches!(
+            protocol.receive(&frame).as_slice(),
+            [Notification::DownloadStart(_)]
+        ));
+    }
+}
@@ -0,0 +1,95 @@
+//! Sending calls again when the link to aria2 broke before they were answered.
+
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::interceptor::{BoxFuture, Interceptor, Next, Request};
+use crate::{Error, Result};
+
+/// Retries failed calls marked [`IDEMPOTENT`](crate::call::Call::IDEMPOTENT),
+/// with an exponential backoff between attempts.
+///
+/// Set with [`ClientBuilder::retry`](crate::ClientBuilder::retry). Other
+/// calls, such as `aria2.addUri`, are sent once whatever happens, as aria2
+/// may have carried them out before the link broke.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: u32,
+    retryable: fn(&Error) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2,
+            retryable: Error::is_transient,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// attempts in total, the first one included, at least 1.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// wait before the second attempt, multiplied by `multiplier` for every
+    /// later one up to `max_backoff`.
+    pub fn backoff(mut self, initial: Duration, max: Duration, multiplier: u32) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self.multiplier = multiplier.max(1);
+        self
+    }
+
+    /// which errors are worth another attempt, [`Error::is_transient`] by default.
+    pub fn retry_if(mut self, retryable: fn(&Error) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// wait after the failed attempt `attempt`, counted from 1.
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.saturating_pow(attempt - 1);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl Interceptor for RetryPolicy {
+    fn intercept<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+        if !request.idempotent {
+            return next.run(request);
+        }
+        Box::pin(async move {
+            let mut attempt = 1;
+            loop {
+                match next.run(request.clone()).await {
+                    Err(e) if attempt < self.max_attempts && (self.retryable)(&e) => {
+                        let delay = self.delay(attempt);
+                        tracing::warn!(
+                            method = %request.method,
+                            "attempt {attempt} failed: {e}, retrying in {delay:?}"
+                        );
+                        crate::runtime::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    result => return result,
+                }
+            }
+        })
+    }
+}
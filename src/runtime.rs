@@ -0,0 +1,96 @@
+//! What the client needs from an async runtime, picked by cargo feature:
+//! `tokio-runtime`, the default, or `smol-runtime`. With both, tokio is used.
+
+use std::future::Future;
+use std::time::Duration;
+
+use crate::ConnectionMeta;
+
+#[cfg(not(any(feature = "tokio-runtime", feature = "smol-runtime")))]
+compile_error!("one of the `tokio-runtime` or `smol-runtime` features must be enabled");
+
+#[cfg(feature = "tokio-runtime")]
+mod imp {
+    use super::*;
+
+    pub(crate) type WsStream = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    pub(crate) async fn connect(meta: &ConnectionMeta) -> tungstenite::Result<WsStream> {
+        let (ws, _) = tokio_tungstenite::connect_async(meta).await?;
+        Ok(ws)
+    }
+
+    pub(crate) fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        tokio::spawn(future);
+    }
+
+    pub(crate) async fn sleep(duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+#[cfg(all(feature = "smol-runtime", not(feature = "tokio-runtime")))]
+mod imp {
+    use super::*;
+    use tungstenite::client::IntoClientRequest;
+    use tungstenite::error::UrlError;
+
+    pub(crate) type WsStream = async_tungstenite::WebSocketStream<smol::net::TcpStream>;
+
+    /// plain `ws://` only, there is no TLS support on this runtime.
+    pub(crate) async fn connect(meta: &ConnectionMeta) -> tungstenite::Result<WsStream> {
+        let request = meta.into_client_request()?;
+        let uri = request.uri();
+        let port = match uri.scheme_str() {
+            Some("ws") => uri.port_u16().unwrap_or(80),
+            Some("wss") => return Err(tungstenite::Error::Url(UrlError::TlsFeatureNotEnabled)),
+            _ => return Err(tungstenite::Error::Url(UrlError::UnsupportedUrlScheme)),
+        };
+        let host = uri
+            .host()
+            .ok_or(tungstenite::Error::Url(UrlError::NoHostName))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let stream = smol::net::TcpStream::connect((host.as_str(), port)).await?;
+        let (ws, _) = async_tungstenite::client_async(request, stream).await?;
+        Ok(ws)
+    }
+
+    pub(crate) fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        smol::spawn(future).detach();
+    }
+
+    pub(crate) async fn sleep(duration: Duration) {
+        smol::Timer::after(duration).await;
+    }
+}
+
+pub(crate) use imp::{connect, sleep, spawn, WsStream};
+
+/// `future` did not complete in time.
+#[derive(Debug)]
+pub(crate) struct Elapsed;
+
+impl std::fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, Elapsed> {
+    let future = std::pin::pin!(future);
+    let sleep = std::pin::pin!(sleep(duration));
+    match futures_util::future::select(future, sleep).await {
+        futures_util::future::Either::Left((output, _)) => Ok(output),
+        futures_util::future::Either::Right(_) => Err(Elapsed),
+    }
+}
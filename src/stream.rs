@@ -0,0 +1,206 @@
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+use futures_util::stream::{self, Stream};
+
+use crate::call::{Call, TellStatusField, TellStatusReply, TellStopped, TellWaiting};
+use crate::{Client, Gid, Result};
+
+/// How [`Client::waiting_stream_with`] and [`Client::stopped_stream_with`] page
+/// through a queue.
+#[derive(Debug, Clone, Copy)]
+pub struct Pagination {
+    page_size: i32,
+    overlap: i32,
+    reverse: bool,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self {
+            page_size: 100,
+            overlap: 1,
+            reverse: false,
+        }
+    }
+}
+
+impl Pagination {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// number of downloads requested per call, at least 1.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// number of already read positions fetched again with every page.
+    ///
+    /// The queue can change while it is being read. Entries inserted before the
+    /// cursor are never returned twice, as every gid is yielded once; entries
+    /// removed before the cursor shift the rest of the queue towards the front,
+    /// and up to `overlap` such removals per page are covered without skipping.
+    pub fn overlap(mut self, overlap: i32) -> Self {
+        self.overlap = overlap.max(0);
+        self
+    }
+
+    /// iterate from the end of the queue to the front, using aria2's negative offsets.
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+}
+
+struct Pager<C> {
+    client: Client,
+    query: fn(i32, i32, Option<BTreeSet<TellStatusField>>) -> C,
+    keys: Option<BTreeSet<TellStatusField>>,
+    pagination: Pagination,
+    cursor: i32,
+    seen: HashSet<Gid>,
+    buffer: VecDeque<TellStatusReply>,
+    done: bool,
+}
+
+impl<C> Pager<C>
+where
+    C: Call<Response = Vec<TellStatusReply>>,
+{
+    async fn next(&mut self) -> Option<Result<TellStatusReply>> {
+        loop {
+            if let Some(reply) = self.buffer.pop_front() {
+                return Some(Ok(reply));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.fetch().await {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+
+    async fn fetch(&mut self) -> Result<()> {
+        let start = (self.cursor - self.pagination.overlap).max(0);
+        let num = self.pagination.page_size + (self.cursor - start);
+        let offset = if self.pagination.reverse {
+            -(start + 1)
+        } else {
+            start
+        };
+
+        let page = self
+            .client
+            .call((self.query)(offset, num, self.keys.clone()))
+            .await?;
+
+        self.done = (page.len() as i32) < num;
+        self.cursor = start + page.len() as i32;
+        for reply in page {
+            match reply.gid {
+                Some(ref gid) if !self.seen.insert(gid.clone()) => {}
+                _ => self.buffer.push_back(reply),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Client {
+    /// all downloads in the waiting queue, see [`TellWaiting`].
+    ///
+    /// `gid` is always requested in addition to `fields`, it is needed to
+    /// tell pages apart.
+    pub fn waiting_stream<I, F>(
+        &self,
+        fields: Option<I>,
+    ) -> impl Stream<Item = Result<TellStatusReply>> + Send + 'static
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<TellStatusField>,
+    {
+        self.waiting_stream_with(fields, Pagination::default())
+    }
+
+    pub fn waiting_stream_with<I, F>(
+        &self,
+        fields: Option<I>,
+        pagination: Pagination,
+    ) -> impl Stream<Item = Result<TellStatusReply>> + Send + 'static
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<TellStatusField>,
+    {
+        self.paginate(
+            |offset, num, keys| TellWaiting::new(offset, num).fields(keys),
+            fields,
+            pagination,
+        )
+    }
+
+    /// all downloads in the stopped queue, see [`TellStopped`].
+    ///
+    /// `gid` is always requested in addition to `fields`, it is needed to
+    /// tell pages apart.
+    pub fn stopped_stream<I, F>(
+        &self,
+        fields: Option<I>,
+    ) -> impl Stream<Item = Result<TellStatusReply>> + Send + 'static
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<TellStatusField>,
+    {
+        self.stopped_stream_with(fields, Pagination::default())
+    }
+
+    pub fn stopped_stream_with<I, F>(
+        &self,
+        fields: Option<I>,
+        pagination: Pagination,
+    ) -> impl Stream<Item = Result<TellStatusReply>> + Send + 'static
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<TellStatusField>,
+    {
+        self.paginate(
+            |offset, num, keys| TellStopped::new(offset, num).fields(keys),
+            fields,
+            pagination,
+        )
+    }
+
+    fn paginate<C, I, F>(
+        &self,
+        query: fn(i32, i32, Option<BTreeSet<TellStatusField>>) -> C,
+        fields: Option<I>,
+        pagination: Pagination,
+    ) -> impl Stream<Item = Result<TellStatusReply>> + Send + 'static
+    where
+        C: Call<Response = Vec<TellStatusReply>> + Send + 'static,
+        I: IntoIterator<Item = F>,
+        F: Into<TellStatusField>,
+    {
+        let keys = fields.map(|fields| {
+            let mut keys: BTreeSet<_> = fields.into_iter().map(Into::into).collect();
+            keys.insert(TellStatusField::Gid);
+            keys
+        });
+        let pager = Pager {
+            client: self.clone(),
+            query,
+            keys,
+            pagination,
+            cursor: 0,
+            seen: HashSet::new(),
+            buffer: VecDeque::new(),
+            done: false,
+        };
+        stream::unfold(pager, |mut pager| async move {
+            let item = pager.next().await?;
+            Some((item, pager))
+        })
+    }
+}
@@ -0,0 +1,882 @@
+//! In-process fake aria2 speaking JSON-RPC over WebSocket, to test code built
+//! on [`Client`](crate::Client) end to end without a running aria2.
+//!
+//! ```ignore
+//! let server = FakeAria2::builder().secret("secret").start().await?;
+//! let (client, mut notifications) = Client::connect(server.meta()).await?;
+//! let gid = client.call(AddUri::uris(vec!["http://example.com/a"])).await?;
+//! server.advance(Duration::from_secs(60)); // downloads progress in virtual time
+//! ```
+//!
+//! Only a subset of aria2 is emulated: the queue state machine, token
+//! checking, gid allocation and notifications. Downloads progress at a fixed
+//! speed when [`FakeAria2::advance`] is called, nothing is fetched.
+//!
+//! The server runs on tokio, also when the client is built for smol: start it
+//! from a tokio runtime kept alive for the duration of the test.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{ConnectionMeta, DownloadErrorCode, Gid};
+
+mod replay;
+
+pub use replay::ReplayServer;
+
+/// Builder of a [`FakeAria2`].
+#[derive(Debug, Clone)]
+pub struct FakeAria2Builder {
+    secret: Option<String>,
+    speed: u64,
+    file_size: u64,
+    piece_length: u64,
+    max_concurrent_downloads: usize,
+}
+
+impl Default for FakeAria2Builder {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            speed: 1024 * 1024,
+            file_size: 16 * 1024 * 1024,
+            piece_length: 1024 * 1024,
+            max_concurrent_downloads: 5,
+        }
+    }
+}
+
+impl FakeAria2Builder {
+    /// `--rpc-secret`
+    pub fn secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// bytes per second of every active download
+    pub fn speed(mut self, speed: u64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// length of every new download
+    pub fn file_size(mut self, file_size: u64) -> Self {
+        self.file_size = file_size;
+        self
+    }
+
+    pub fn piece_length(mut self, piece_length: u64) -> Self {
+        self.piece_length = piece_length.max(1);
+        self
+    }
+
+    /// `--max-concurrent-downloads`
+    pub fn max_concurrent_downloads(mut self, max: usize) -> Self {
+        self.max_concurrent_downloads = max.max(1);
+        self
+    }
+
+    /// listen on a random local port
+    pub async fn start(self) -> std::io::Result<FakeAria2> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (events, _) = broadcast::channel(1024);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::new(self)),
+            events,
+        });
+        let accept = tokio::spawn(Self::accept(listener, shared.clone()));
+        Ok(FakeAria2 {
+            addr,
+            shared,
+            accept,
+        })
+    }
+
+    async fn accept(listener: TcpListener, shared: Arc<Shared>) {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    // such as too many open files, which takes a while to pass
+                    tracing::warn!("fake aria2 accept error: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            if shared.state.lock().unwrap().faults.offline {
+                drop(stream);
+                continue;
+            }
+            tokio::spawn(serve(stream, shared.clone()));
+        }
+    }
+}
+
+/// Handle to a running fake aria2, stopped on drop.
+pub struct FakeAria2 {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    accept: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for FakeAria2 {
+    fn drop(&mut self) {
+        self.accept.abort();
+        let _ = self.shared.events.send(Event::Disconnect);
+    }
+}
+
+impl FakeAria2 {
+    pub fn builder() -> FakeAria2Builder {
+        FakeAria2Builder::default()
+    }
+
+    pub async fn start() -> std::io::Result<Self> {
+        Self::builder().start().await
+    }
+
+    pub fn url(&self) -> String {
+        format!("ws://{}/jsonrpc", self.addr)
+    }
+
+    /// connection to this server with its current secret
+    pub fn meta(&self) -> ConnectionMeta {
+        let state = self.shared.state.lock().unwrap();
+        ConnectionMeta::new(&self.url(), state.secret.as_deref())
+    }
+
+    /// let `elapsed` pass for all active downloads, completing those done and
+    /// starting waiting ones in their place.
+    pub fn advance(&self, elapsed: Duration) {
+        let mut state = self.shared.state.lock().unwrap();
+        let notifications = state.advance(elapsed);
+        self.shared.broadcast(notifications);
+    }
+
+    /// status of a download as `aria2.tellStatus` would report it
+    pub fn status(&self, gid: &Gid) -> Option<Value> {
+        let state = self.shared.state.lock().unwrap();
+        state.downloads.get(gid).map(|d| d.to_json(&state.config))
+    }
+
+    /// make a download fail with `code`
+    pub fn fail_download(&self, gid: &Gid, code: DownloadErrorCode) {
+        let mut state = self.shared.state.lock().unwrap();
+        let notifications = state.fail(gid, code);
+        self.shared.broadcast(notifications);
+    }
+
+    /// close every open connection without a close frame
+    pub fn drop_connections(&self) {
+        let _ = self.shared.events.send(Event::Disconnect);
+    }
+
+    /// refuse new connections while `offline`, existing ones are kept
+    pub fn set_offline(&self, offline: bool) {
+        self.shared.state.lock().unwrap().faults.offline = offline;
+    }
+
+    /// delay every reply by `delay`
+    pub fn set_reply_delay(&self, delay: Duration) {
+        self.shared.state.lock().unwrap().faults.reply_delay = delay;
+    }
+
+    /// answer the next call of `method` with an error
+    pub fn fail_next(&self, method: impl Into<String>, code: i64, message: impl Into<String>) {
+        self.shared
+            .state
+            .lock()
+            .unwrap()
+            .faults
+            .errors
+            .entry(method.into())
+            .or_default()
+            .push_back((code, message.into()));
+    }
+
+    /// emulate a restart of aria2 without a session file: a new session id,
+    /// all downloads gone, and every connection dropped.
+    pub fn restart(&self) {
+        self.shared.state.lock().unwrap().restart();
+        self.drop_connections();
+    }
+
+    /// methods received so far, in order
+    pub fn received(&self) -> Vec<String> {
+        self.shared.state.lock().unwrap().received.clone()
+    }
+
+    /// number of WebSocket connections open
+    pub fn connections(&self) -> usize {
+        self.shared.events.receiver_count()
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Event {
+    Notification(String),
+    Disconnect,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    events: broadcast::Sender<Event>,
+}
+
+impl Shared {
+    fn broadcast(&self, notifications: Vec<(&'static str, Gid)>) {
+        for (method, gid) in notifications {
+            let text = json!({
+                "jsonrpc": "2.0",
+                "method": method,
+                "params": [{"gid": gid}],
+            });
+            let _ = self.events.send(Event::Notification(text.to_string()));
+        }
+    }
+}
+
+async fn serve(stream: tokio::net::TcpStream, shared: Arc<Shared>) {
+    let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut tx, mut rx) = ws.split();
+    let mut events = shared.events.subscribe();
+    loop {
+        tokio::select! {
+            msg = rx.next() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) => {
+                        // flush the close frame answering the client's
+                        let _ = tx.close().await;
+                        return;
+                    }
+                    None | Some(Err(_)) => return,
+                    Some(Ok(_)) => continue,
+                };
+                let (reply, notifications, delay) = {
+                    let mut state = shared.state.lock().unwrap();
+                    let (reply, notifications) = state.handle(&text);
+                    (reply, notifications, state.faults.reply_delay)
+                };
+                if !delay.is_zero() {
+                    // events still come in while the reply is held back, a
+                    // disconnect loses it
+                    let sleep = tokio::time::sleep(delay);
+                    tokio::pin!(sleep);
+                    loop {
+                        tokio::select! {
+                            _ = &mut sleep => break,
+                            event = events.recv() => {
+                                if !forward(&mut tx, event).await {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                }
+                if tx.send(Message::Text(reply.to_string().into())).await.is_err() {
+                    return;
+                }
+                shared.broadcast(notifications);
+            }
+            event = events.recv() => {
+                if !forward(&mut tx, event).await {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// pass an event on to the client, false once the connection is to be closed.
+async fn forward<S>(tx: &mut S, event: Result<Event, broadcast::error::RecvError>) -> bool
+where
+    S: futures_util::Sink<Message> + Unpin,
+{
+    match event {
+        Ok(Event::Notification(text)) => tx.send(Message::Text(text.into())).await.is_ok(),
+        Ok(Event::Disconnect) | Err(broadcast::error::RecvError::Closed) => false,
+        Err(broadcast::error::RecvError::Lagged(_)) => true,
+    }
+}
+
+#[derive(Default)]
+struct Faults {
+    offline: bool,
+    reply_delay: Duration,
+    errors: HashMap<String, VecDeque<(i64, String)>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Active,
+    Waiting,
+    Paused,
+    Error(DownloadErrorCode),
+    Complete,
+    Removed,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Waiting => "waiting",
+            Self::Paused => "paused",
+            Self::Error(_) => "error",
+            Self::Complete => "complete",
+            Self::Removed => "removed",
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        matches!(self, Self::Error(_) | Self::Complete | Self::Removed)
+    }
+}
+
+struct Download {
+    gid: Gid,
+    status: Status,
+    uris: Vec<String>,
+    dir: String,
+    out: String,
+    total_length: u64,
+    completed_length: u64,
+    options: serde_json::Map<String, Value>,
+}
+
+impl Download {
+    fn to_json(&self, config: &FakeAria2Builder) -> Value {
+        let piece_length = config.piece_length;
+        let num_pieces = self.total_length.div_ceil(piece_length);
+        let completed_pieces = if self.completed_length == self.total_length {
+            num_pieces
+        } else {
+            self.completed_length / piece_length
+        };
+        let mut bits = vec![0u8; num_pieces.div_ceil(8) as usize];
+        for i in 0..completed_pieces {
+            bits[(i / 8) as usize] |= 0x80 >> (i % 8);
+        }
+        let bitfield: String = bits.iter().map(|b| format!("{b:02x}")).collect();
+        let active = self.status == Status::Active;
+        let path = format!("{}/{}", self.dir, self.out);
+
+        let mut status = json!({
+            "gid": self.gid,
+            "status": self.status.as_str(),
+            "totalLength": self.total_length.to_string(),
+            "completedLength": self.completed_length.to_string(),
+            "uploadLength": "0",
+            "bitfield": bitfield,
+            "downloadSpeed": if active { config.speed } else { 0 }.to_string(),
+            "uploadSpeed": "0",
+            "pieceLength": piece_length.to_string(),
+            "numPieces": num_pieces.to_string(),
+            "connections": if active { "1" } else { "0" },
+            "dir": self.dir,
+            "files": [{
+                "index": "1",
+                "path": path,
+                "length": self.total_length.to_string(),
+                "completedLength": self.completed_length.to_string(),
+                "selected": "true",
+                "uris": self.uris_json(),
+            }],
+        });
+        if let Status::Error(code) = self.status {
+            status["errorCode"] = code.code().to_string().into();
+            status["errorMessage"] = code.to_string().into();
+        }
+        status
+    }
+
+    fn uris_json(&self) -> Value {
+        let used = self.status == Status::Active;
+        self.uris
+            .iter()
+            .map(|uri| json!({"uri": uri, "status": if used { "used" } else { "waiting" }}))
+            .collect()
+    }
+}
+
+struct State {
+    config: FakeAria2Builder,
+    secret: Option<String>,
+    session_id: u64,
+    next_gid: u64,
+    downloads: HashMap<Gid, Download>,
+    /// active and waiting downloads, in queue order
+    queue: Vec<Gid>,
+    stopped: Vec<Gid>,
+    global_options: serde_json::Map<String, Value>,
+    faults: Faults,
+    received: Vec<String>,
+}
+
+type Notifications = Vec<(&'static str, Gid)>;
+
+struct RpcFailure(i64, String);
+
+impl RpcFailure {
+    fn new(message: impl Into<String>) -> Self {
+        Self(1, message.into())
+    }
+}
+
+type RpcResult = std::result::Result<Value, RpcFailure>;
+
+impl State {
+    fn new(config: FakeAria2Builder) -> Self {
+        Self {
+            secret: config.secret.clone(),
+            config,
+            session_id: 1,
+            next_gid: 1,
+            downloads: HashMap::new(),
+            queue: Vec::new(),
+            stopped: Vec::new(),
+            global_options: serde_json::Map::new(),
+            faults: Faults::default(),
+            received: Vec::new(),
+        }
+    }
+
+    fn restart(&mut self) {
+        self.session_id += 1;
+        self.downloads.clear();
+        self.queue.clear();
+        self.stopped.clear();
+    }
+
+    fn handle(&mut self, text: &str) -> (Value, Notifications) {
+        let request: Value = match serde_json::from_str(text) {
+            Ok(request) => request,
+            Err(_) => {
+                let error = json!({"code": -32700, "message": "Parse error."});
+                return (json!({"jsonrpc": "2.0", "id": null, "error": error}), vec![]);
+            }
+        };
+        let id = request["id"].clone();
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        let params = match request.get("params") {
+            Some(Value::Array(params)) => params.clone(),
+            _ => vec![],
+        };
+        self.received.push(method.clone());
+
+        let mut notifications = vec![];
+        let result = self.dispatch(&method, params, &mut notifications);
+        let reply = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(RpcFailure(code, message)) => {
+                json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+            }
+        };
+        (reply, notifications)
+    }
+
+    fn dispatch(
+        &mut self,
+        method: &str,
+        mut params: Vec<Value>,
+        notifications: &mut Notifications,
+    ) -> RpcResult {
+        if let Some((code, message)) = self
+            .faults
+            .errors
+            .get_mut(method)
+            .and_then(VecDeque::pop_front)
+        {
+            return Err(RpcFailure(code, message));
+        }
+
+        if !method.starts_with("system.") {
+            let token = match params.first().and_then(Value::as_str) {
+                Some(token) if token.starts_with("token:") => {
+                    let token = token["token:".len()..].to_string();
+                    params.remove(0);
+                    Some(token)
+                }
+                _ => None,
+            };
+            if self.secret.is_some() && token != self.secret {
+                return Err(RpcFailure::new("Unauthorized"));
+            }
+        }
+
+        let mut params = params.into_iter();
+        let mut next = || params.next().unwrap_or(Value::Null);
+        match method {
+            "system.listMethods" => Ok(json!(METHODS)),
+            "system.listNotifications" => Ok(json!(NOTIFICATIONS)),
+            "aria2.getVersion" => Ok(json!({"version": "1.37.0", "enabledFeatures": []})),
+            "aria2.getSessionInfo" => {
+                Ok(json!({"sessionId": format!("{:040x}", self.session_id)}))
+            }
+            "aria2.addUri" => {
+                let (uris, options, position) = (next(), next(), next());
+                self.add_uri(uris, options, position, notifications)
+            }
+            "aria2.tellStatus" => {
+                let (gid, keys) = (self.gid(next())?, next());
+                let download = self.download(&gid)?;
+                Ok(filter_keys(download.to_json(&self.config), &keys))
+            }
+            "aria2.tellActive" => {
+                let keys = next();
+                let active: Vec<_> = self
+                    .queue
+                    .iter()
+                    .filter(|gid| self.downloads[*gid].status == Status::Active)
+                    .cloned()
+                    .collect();
+                Ok(self.list(&active, 0, active.len() as i64, &keys))
+            }
+            "aria2.tellWaiting" => {
+                let (offset, num, keys) = (next(), next(), next());
+                let waiting: Vec<_> = self
+                    .queue
+                    .iter()
+                    .filter(|gid| self.downloads[*gid].status != Status::Active)
+                    .cloned()
+                    .collect();
+                Ok(self.list(&waiting, int(&offset)?, int(&num)?, &keys))
+            }
+            "aria2.tellStopped" => {
+                let (offset, num, keys) = (next(), next(), next());
+                let stopped = self.stopped.clone();
+                Ok(self.list(&stopped, int(&offset)?, int(&num)?, &keys))
+            }
+            "aria2.getUris" => {
+                let gid = self.gid(next())?;
+                Ok(self.download(&gid)?.uris_json())
+            }
+            "aria2.getFiles" => {
+                let gid = self.gid(next())?;
+                let status = self.download(&gid)?.to_json(&self.config);
+                Ok(status["files"].clone())
+            }
+            "aria2.getOption" => {
+                let gid = self.gid(next())?;
+                let download = self.download(&gid)?;
+                let mut options = download.options.clone();
+                options.insert("dir".into(), download.dir.clone().into());
+                options.insert("out".into(), download.out.clone().into());
+                Ok(Value::Object(options))
+            }
+            "aria2.pause" | "aria2.forcePause" => {
+                let gid = self.gid(next())?;
+                self.pause(&gid, notifications)
+            }
+            "aria2.unpause" => {
+                let gid = self.gid(next())?;
+                self.unpause(&gid, notifications)
+            }
+            "aria2.remove" | "aria2.forceRemove" => {
+                let gid = self.gid(next())?;
+                self.remove(&gid, notifications)
+            }
+            "aria2.removeDownloadResult" => {
+                let gid = self.gid(next())?;
+                if !self.stopped.contains(&gid) {
+                    return Err(RpcFailure::new(format!("Could not remove download result of GID#{gid}")));
+                }
+                self.stopped.retain(|g| *g != gid);
+                self.downloads.remove(&gid);
+                Ok(json!("OK"))
+            }
+            "aria2.purgeDownloadResult" => {
+                for gid in std::mem::take(&mut self.stopped) {
+                    self.downloads.remove(&gid);
+                }
+                Ok(json!("OK"))
+            }
+            "aria2.getGlobalOption" => Ok(Value::Object(self.global_options.clone())),
+            "aria2.changeGlobalOption" => {
+                let Value::Object(options) = next() else {
+                    return Err(RpcFailure(-32602, "Invalid params.".into()));
+                };
+                for (key, value) in options {
+                    if key == "rpc-secret" {
+                        self.secret = value.as_str().map(str::to_string);
+                    } else {
+                        self.global_options.insert(key, value);
+                    }
+                }
+                Ok(json!("OK"))
+            }
+            _ => Err(RpcFailure(-32601, "Method not found.".into())),
+        }
+    }
+
+    fn gid(&self, value: Value) -> std::result::Result<Gid, RpcFailure> {
+        let s = value.as_str().unwrap_or_default();
+        s.parse()
+            .map_err(|_| RpcFailure::new(format!("Invalid GID {s}")))
+    }
+
+    fn download(&self, gid: &Gid) -> std::result::Result<&Download, RpcFailure> {
+        self.downloads
+            .get(gid)
+            .ok_or_else(|| RpcFailure::new(format!("GID {gid} is not found")))
+    }
+
+    fn list(&self, gids: &[Gid], offset: i64, num: i64, keys: &Value) -> Value {
+        let len = gids.len() as i64;
+        let picked: Vec<&Gid> = if offset >= 0 {
+            gids.iter()
+                .skip(offset as usize)
+                .take(num.max(0) as usize)
+                .collect()
+        } else {
+            let start = len + offset;
+            (0..num.max(0))
+                .map(|i| start - i)
+                .take_while(|&i| i >= 0)
+                .filter(|&i| i < len)
+                .map(|i| &gids[i as usize])
+                .collect()
+        };
+        picked
+            .into_iter()
+            .map(|gid| filter_keys(self.downloads[gid].to_json(&self.config), keys))
+            .collect()
+    }
+
+    fn add_uri(
+        &mut self,
+        uris: Value,
+        options: Value,
+        position: Value,
+        notifications: &mut Notifications,
+    ) -> RpcResult {
+        let uris: Vec<String> = serde_json::from_value(uris)
+            .map_err(|_| RpcFailure::new("URIs must be an array of string"))?;
+        if uris.is_empty() {
+            return Err(RpcFailure::new("No URI to download."));
+        }
+        let options = match options {
+            Value::Object(options) => options,
+            _ => serde_json::Map::new(),
+        };
+
+        let gid = match options.get("gid").and_then(Value::as_str) {
+            Some(gid) => {
+                let gid: Gid = gid
+                    .parse()
+                    .map_err(|_| RpcFailure::new(format!("Invalid GID {gid}")))?;
+                if self.downloads.contains_key(&gid) {
+                    return Err(RpcFailure::new(format!("GID {gid} is not unique.")));
+                }
+                gid
+            }
+            None => loop {
+                let gid = Gid::new(self.next_gid.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+                self.next_gid += 1;
+                if !self.downloads.contains_key(&gid) {
+                    break gid;
+                }
+            },
+        };
+
+        let out = options
+            .get("out")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                let name = uris[0].rsplit('/').next().unwrap_or_default();
+                if name.is_empty() { "index.html" } else { name }.to_string()
+            });
+        let dir = options
+            .get("dir")
+            .and_then(Value::as_str)
+            .unwrap_or(".")
+            .to_string();
+        let paused = options.get("pause").and_then(Value::as_str) == Some("true");
+
+        self.downloads.insert(
+            gid.clone(),
+            Download {
+                gid: gid.clone(),
+                status: if paused { Status::Paused } else { Status::Waiting },
+                uris,
+                dir,
+                out,
+                total_length: self.config.file_size,
+                completed_length: 0,
+                options,
+            },
+        );
+        let position = position
+            .as_i64()
+            .map(|p| (p.max(0) as usize).min(self.queue.len()))
+            .unwrap_or(self.queue.len());
+        self.queue.insert(position, gid.clone());
+        self.schedule(notifications);
+        Ok(json!(gid))
+    }
+
+    fn pause(&mut self, gid: &Gid, notifications: &mut Notifications) -> RpcResult {
+        let download = self.downloads.get_mut(gid).filter(|d| !d.status.is_stopped());
+        let Some(download) = download else {
+            return Err(RpcFailure::new(format!("GID#{gid} cannot be paused now")));
+        };
+        if download.status != Status::Paused {
+            download.status = Status::Paused;
+            notifications.push(("aria2.onDownloadPause", gid.clone()));
+        }
+        self.schedule(notifications);
+        Ok(json!(gid))
+    }
+
+    fn unpause(&mut self, gid: &Gid, notifications: &mut Notifications) -> RpcResult {
+        let download = self.downloads.get_mut(gid).filter(|d| d.status == Status::Paused);
+        let Some(download) = download else {
+            return Err(RpcFailure::new(format!("GID#{gid} cannot be unpaused now")));
+        };
+        download.status = Status::Waiting;
+        self.schedule(notifications);
+        Ok(json!(gid))
+    }
+
+    fn remove(&mut self, gid: &Gid, notifications: &mut Notifications) -> RpcResult {
+        if !self.queue.contains(gid) {
+            return Err(RpcFailure::new(format!("Active Download not found for GID#{gid}")));
+        }
+        self.stop(gid, Status::Removed, notifications);
+        self.schedule(notifications);
+        Ok(json!(gid))
+    }
+
+    fn fail(&mut self, gid: &Gid, code: DownloadErrorCode) -> Notifications {
+        let mut notifications = vec![];
+        if self.queue.contains(gid) {
+            self.stop(gid, Status::Error(code), &mut notifications);
+            self.schedule(&mut notifications);
+        }
+        notifications
+    }
+
+    fn stop(&mut self, gid: &Gid, status: Status, notifications: &mut Notifications) {
+        self.queue.retain(|g| g != gid);
+        self.stopped.push(gid.clone());
+        if let Some(download) = self.downloads.get_mut(gid) {
+            download.status = status;
+        }
+        let method = match status {
+            Status::Complete => "aria2.onDownloadComplete",
+            Status::Error(_) => "aria2.onDownloadError",
+            _ => "aria2.onDownloadStop",
+        };
+        notifications.push((method, gid.clone()));
+    }
+
+    /// start waiting downloads while there are free slots
+    fn schedule(&mut self, notifications: &mut Notifications) {
+        let mut active = self
+            .queue
+            .iter()
+            .filter(|gid| self.downloads[*gid].status == Status::Active)
+            .count();
+        for gid in &self.queue {
+            if active >= self.config.max_concurrent_downloads {
+                break;
+            }
+            let download = self.downloads.get_mut(gid).unwrap();
+            if download.status == Status::Waiting {
+                download.status = Status::Active;
+                active += 1;
+                notifications.push(("aria2.onDownloadStart", gid.clone()));
+            }
+        }
+    }
+
+    fn advance(&mut self, elapsed: Duration) -> Notifications {
+        let mut notifications = vec![];
+        let progress = (self.config.speed as f64 * elapsed.as_secs_f64()) as u64;
+        let mut completed = vec![];
+        for gid in &self.queue {
+            let download = self.downloads.get_mut(gid).unwrap();
+            if download.status == Status::Active {
+                download.completed_length =
+                    (download.completed_length + progress).min(download.total_length);
+                if download.completed_length == download.total_length {
+                    completed.push(gid.clone());
+                }
+            }
+        }
+        for gid in completed {
+            self.stop(&gid, Status::Complete, &mut notifications);
+        }
+        self.schedule(&mut notifications);
+        notifications
+    }
+}
+
+fn int(value: &Value) -> std::result::Result<i64, RpcFailure> {
+    value
+        .as_i64()
+        .ok_or_else(|| RpcFailure(-32602, "Invalid params.".into()))
+}
+
+fn filter_keys(status: Value, keys: &Value) -> Value {
+    let (Value::Object(status), Value::Array(keys)) = (&status, keys) else {
+        return status;
+    };
+    if keys.is_empty() {
+        return Value::Object(status.clone());
+    }
+    let keys: Vec<&str> = keys.iter().filter_map(Value::as_str).collect();
+    Value::Object(
+        status
+            .iter()
+            .filter(|(k, _)| keys.contains(&k.as_str()))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+    )
+}
+
+const METHODS: &[&str] = &[
+    "aria2.addUri",
+    "aria2.remove",
+    "aria2.forceRemove",
+    "aria2.pause",
+    "aria2.forcePause",
+    "aria2.unpause",
+    "aria2.tellStatus",
+    "aria2.getUris",
+    "aria2.getFiles",
+    "aria2.tellActive",
+    "aria2.tellWaiting",
+    "aria2.tellStopped",
+    "aria2.getOption",
+    "aria2.getGlobalOption",
+    "aria2.changeGlobalOption",
+    "aria2.purgeDownloadResult",
+    "aria2.removeDownloadResult",
+    "aria2.getVersion",
+    "aria2.getSessionInfo",
+    "system.listMethods",
+    "system.listNotifications",
+];
+
+const NOTIFICATIONS: &[&str] = &[
+    "aria2.onDownloadStart",
+    "aria2.onDownloadPause",
+    "aria2.onDownloadStop",
+    "aria2.onDownloadComplete",
+    "aria2.onDownloadError",
+    "aria2.onBtDownloadComplete",
+];
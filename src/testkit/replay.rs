@@ -0,0 +1,200 @@
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::cassette::{self, Direction, Entry};
+use crate::ConnectionMeta;
+
+/// A request of the cassette with what aria2 answered.
+struct Interaction {
+    method: String,
+    params: Vec<Value>,
+    /// the response frame, without its id
+    response: Option<Value>,
+    /// notifications received after the response and before the next one
+    notifications: Vec<Value>,
+    used: bool,
+}
+
+struct Script {
+    /// notifications received before any response
+    initial: Vec<Value>,
+    interactions: Vec<Interaction>,
+    unmatched: Vec<(String, Vec<Value>)>,
+}
+
+impl Script {
+    fn new(entries: Vec<Entry>) -> Self {
+        let mut initial = Vec::new();
+        let mut interactions: Vec<Interaction> = Vec::new();
+        let mut last_response: Option<usize> = None;
+        let mut ids = std::collections::HashMap::new();
+
+        for Entry { direction, frame } in entries {
+            match direction {
+                Direction::Sent => {
+                    ids.insert(frame["id"].to_string(), interactions.len());
+                    interactions.push(Interaction {
+                        method: frame["method"].as_str().unwrap_or_default().to_string(),
+                        params: cassette::untokenized_params(&frame),
+                        response: None,
+                        notifications: Vec::new(),
+                        used: false,
+                    });
+                }
+                Direction::Received if frame.get("method").is_some() => match last_response {
+                    Some(index) => interactions[index].notifications.push(frame),
+                    None => initial.push(frame),
+                },
+                Direction::Received => {
+                    if let Some(&index) = ids.get(&frame["id"].to_string()) {
+                        let mut response = frame;
+                        if let Some(object) = response.as_object_mut() {
+                            object.remove("id");
+                        }
+                        interactions[index].response = Some(response);
+                        last_response = Some(index);
+                    }
+                }
+            }
+        }
+        Self {
+            initial,
+            interactions,
+            unmatched: Vec::new(),
+        }
+    }
+
+    /// the first unused interaction of the same method and params, or the
+    /// last used one if all were, for requests repeated more than recorded.
+    fn answer(&mut self, method: &str, params: Vec<Value>) -> Option<(Value, Vec<Value>)> {
+        let candidates: Vec<usize> = self
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.method == method && i.params == params && i.response.is_some())
+            .map(|(n, _)| n)
+            .collect();
+        let index = candidates
+            .iter()
+            .copied()
+            .find(|&n| !self.interactions[n].used)
+            .or(candidates.last().copied());
+        match index.map(|n| &mut self.interactions[n]) {
+            Some(interaction) => {
+                let notifications = if interaction.used {
+                    Vec::new()
+                } else {
+                    interaction.notifications.clone()
+                };
+                interaction.used = true;
+                Some((interaction.response.clone()?, notifications))
+            }
+            None => {
+                self.unmatched.push((method.to_string(), params));
+                None
+            }
+        }
+    }
+}
+
+/// Serves a cassette recorded with
+/// [`ClientBuilder::record`](crate::ClientBuilder::record) back over WebSocket.
+///
+/// Requests are matched by method and params, the token ignored. A matched
+/// request gets the recorded response, followed by the notifications
+/// recorded right after it. An unmatched one gets an error, and is reported
+/// by [`ReplayServer::unmatched`].
+pub struct ReplayServer {
+    addr: SocketAddr,
+    script: Arc<Mutex<Script>>,
+    accept: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for ReplayServer {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+impl ReplayServer {
+    pub async fn start<P: AsRef<Path>>(cassette: P) -> std::io::Result<Self> {
+        Self::from_entries(cassette::read(cassette)?).await
+    }
+
+    pub async fn from_entries(entries: Vec<Entry>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let script = Arc::new(Mutex::new(Script::new(entries)));
+        let accept = tokio::spawn({
+            let script = script.clone();
+            async move {
+                loop {
+                    if let Ok((stream, _)) = listener.accept().await {
+                        tokio::spawn(serve(stream, script.clone()));
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            addr,
+            script,
+            accept,
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("ws://{}/jsonrpc", self.addr)
+    }
+
+    /// the token is not checked, any will do
+    pub fn meta(&self) -> ConnectionMeta {
+        ConnectionMeta::new(&self.url(), None)
+    }
+
+    /// requests without a recorded counterpart, as method and params
+    pub fn unmatched(&self) -> Vec<(String, Vec<Value>)> {
+        self.script.lock().unwrap().unmatched.clone()
+    }
+}
+
+async fn serve(stream: tokio::net::TcpStream, script: Arc<Mutex<Script>>) {
+    let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let initial = std::mem::take(&mut script.lock().unwrap().initial);
+    for notification in initial {
+        if ws.send(Message::Text(notification.to_string().into())).await.is_err() {
+            return;
+        }
+    }
+
+    while let Some(Ok(msg)) = ws.next().await {
+        let Message::Text(text) = msg else {
+            continue;
+        };
+        let Ok(request) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = cassette::untokenized_params(&request);
+        let answer = script.lock().unwrap().answer(method, params);
+
+        let (mut response, notifications) = answer.unwrap_or_else(|| {
+            let message = format!("no recorded response for {method}");
+            (json!({"jsonrpc": "2.0", "error": {"code": 1, "message": message}}), vec![])
+        });
+        response["id"] = request["id"].clone();
+        let frames = std::iter::once(response).chain(notifications);
+        for frame in frames {
+            if ws.send(Message::Text(frame.to_string().into())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -1,71 +1,140 @@
-use futures_util::{stream::SplitSink, SinkExt, StreamExt};
-use tokio::sync::{mpsc, oneshot};
-use tokio::time::{timeout, Duration};
-use tokio_tungstenite::tungstenite;
+use futures_util::{stream::SplitSink, stream::SplitStream, FutureExt, SinkExt, StreamExt};
+use tokio::sync::{mpsc, oneshot, watch};
 use std::ops::Deref;
-use std::sync::Arc;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::Instrument;
 
-use crate::call::Call;
+use crate::call::{Call, ChangeGlobalOption, RawCall};
+use crate::cassette::{Direction, Recorder};
 use crate::error::Error;
-use crate::jsonrpc;
-use crate::Result;
-
-type WSMessage = tokio_tungstenite::tungstenite::Message;
-type WSStream =
-    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
-
-#[derive(Debug, Clone)]
-pub enum Notification {
-    DownloadStart(String),
-    DownloadPause(String),
-    DownloadStop(String),
-    DownloadComplete(String),
-    DownloadError(String),
-    BtDownloadComplete(String),
-}
-
-impl Notification {
-    pub fn new(method: &str, gid: String) -> Self {
-        match method {
-            "aria2.onDownloadStart" => Self::DownloadStart(gid),
-            "aria2.onDownloadPause" => Self::DownloadPause(gid),
-            "aria2.onDownloadStop" => Self::DownloadStop(gid),
-            "aria2.onDownloadComplete" => Self::DownloadComplete(gid),
-            "aria2.onDownloadError" => Self::DownloadError(gid),
-            "aria2.onBtDownloadComplete" => Self::BtDownloadComplete(gid),
-            _ => unreachable!(),
-        }
-    }
-}
-
-#[derive(serde::Deserialize)]
-struct NotificationParam {
-    gid: String,
-}
+use crate::interceptor::{Interceptor, Next, Request};
+use crate::options::Aria2Options;
+use crate::limit::{Limiter, RateLimit};
+use crate::protocol::{Notification, Protocol, RPCReponse, RPCRequest};
+use crate::retry::RetryPolicy;
+use crate::runtime::{self, timeout, WsStream};
+use crate::secret::{redact_url, RedactedParams};
+use crate::{Result, Secret};
 
-struct RPCRequest {
-    params: Option<serde_json::Value>,
-    method: &'static str,
-    handler: oneshot::Sender<RPCReponse>,
-}
+type WSMessage = tungstenite::Message;
 
-enum RPCReponse {
-    Success(serde_json::Value),
-    Error(jsonrpc::Error),
-}
+/// how long [`ClientInner::close`] waits for the calls in flight.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Where to reach aria2, and with which secret.
+///
+/// Also parsed from a single string, so a config file can hold one per
+/// instance, see the [`FromStr`](std::str::FromStr) impl.
+#[derive(Debug, Clone, serde_with::DeserializeFromStr)]
 pub struct ConnectionMeta {
     pub url: String,
-    pub token: Option<String>,
+    /// `token:<rpc-secret>`, as put first in the params
+    pub token: Option<Secret>,
 }
 
 impl ConnectionMeta{
     pub fn new(url: &str, token: Option<&str>) -> Self {
         Self {
             url: url.to_string(),
-            token: token.map(|s| format!("token:{}", s)),
+            token: token.map(|s| Secret::new(format!("token:{}", s))),
+        }
+    }
+}
+
+/// aria2's `--rpc-listen-port` default.
+pub(crate) const DEFAULT_PORT: u16 = 6800;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid aria2 url: {0}")]
+pub struct ParseConnectionMetaError(&'static str);
+
+/// Accepts the forms aria2 front ends use:
+///
+/// - `ws://host:6800/jsonrpc` and `wss://`, as is
+/// - `http://` and `https://`, mapped to `ws://` and `wss://`
+/// - `host:port` or `host`, on `ws://`, port 6800 and path `/jsonrpc` by default
+/// - `ws://token:<rpc-secret>@host:6800/jsonrpc`, the secret as in AriaNg links,
+///   percent-decoded and kept out of [`ConnectionMeta::url`]
+impl std::str::FromStr for ConnectionMeta {
+    type Err = ParseConnectionMetaError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let (scheme, rest) = match s.split_once("://") {
+            Some((scheme, rest)) => match scheme.to_ascii_lowercase().as_str() {
+                "ws" | "http" => ("ws", rest),
+                "wss" | "https" => ("wss", rest),
+                _ => return Err(ParseConnectionMetaError("scheme must be ws, wss, http or https")),
+            },
+            None => ("ws", s),
+        };
+        let (authority, path) = match rest.find(['/', '?', '#']) {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+        let (userinfo, host_port) = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => (Some(userinfo), host_port),
+            None => (None, authority),
+        };
+        let secret = match userinfo {
+            None => None,
+            Some(userinfo) => match userinfo.split_once(':') {
+                Some(("token", secret)) => Some(percent_decode(secret)?),
+                _ => return Err(ParseConnectionMetaError("credentials must be token:<rpc-secret>")),
+            },
+        };
+
+        // the port follows the closing bracket of an ipv6 address
+        let host_end = host_port.rfind(']').map_or(0, |i| i + 1);
+        let (host, port) = match host_port[host_end..].rfind(':') {
+            Some(i) => {
+                let (host, port) = host_port.split_at(host_end + i);
+                let port = port[1..]
+                    .parse::<u16>()
+                    .map_err(|_| ParseConnectionMetaError("invalid port"))?;
+                (host, port)
+            }
+            None => (host_port, DEFAULT_PORT),
+        };
+        if host.is_empty() {
+            return Err(ParseConnectionMetaError("missing host"));
+        }
+        let path = match path {
+            "" | "/" => "/jsonrpc".to_string(),
+            path if !path.starts_with('/') => format!("/jsonrpc{path}"),
+            path => path.to_string(),
+        };
+        Ok(Self::new(&format!("{scheme}://{host}:{port}{path}"), secret.as_deref()))
+    }
+}
+
+fn percent_decode(s: &str) -> std::result::Result<String, ParseConnectionMetaError> {
+    let invalid = ParseConnectionMetaError("invalid percent-encoding in the secret");
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b != b'%' {
+            bytes.push(b);
+            rest = tail;
+            continue;
+        }
+        // to_digit takes only ascii hex digits, unlike from_str_radix which takes a sign
+        let hex = |b: &u8| (*b as char).to_digit(16);
+        match tail {
+            [hi, lo, tail @ ..] => match (hex(hi), hex(lo)) {
+                (Some(hi), Some(lo)) => {
+                    bytes.push((hi * 16 + lo) as u8);
+                    rest = tail;
+                }
+                _ => return Err(invalid),
+            },
+            _ => return Err(invalid),
         }
     }
+    String::from_utf8(bytes).map_err(|_| invalid)
 }
 
 impl tungstenite::client::IntoClientRequest for &ConnectionMeta{
@@ -75,6 +144,25 @@ impl tungstenite::client::IntoClientRequest for &ConnectionMeta{
     }
 }
 
+/// `token:<rpc-secret>` shared by the client and its background task,
+/// replaced as a whole on rotation.
+#[derive(Clone)]
+struct SharedToken(Arc<RwLock<Option<Secret>>>);
+
+impl SharedToken {
+    fn new(token: Option<Secret>) -> Self {
+        Self(Arc::new(RwLock::new(token)))
+    }
+
+    fn get(&self) -> Option<Secret> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn set(&self, token: Option<Secret>) {
+        *self.0.write().unwrap() = token;
+    }
+}
+
 #[derive(Clone)]
 pub struct Client {
     inner: Arc<ClientInner>,
@@ -90,7 +178,67 @@ impl Deref for Client {
 
 impl Client {
     pub async fn connect(meta: ConnectionMeta) -> Result<(Self, mpsc::UnboundedReceiver<Notification>)>{
-        let (inner, notify_rx) = ClientInner::connect(meta).await?;
+        Self::builder(meta).connect().await
+    }
+
+    pub fn builder(meta: ConnectionMeta) -> ClientBuilder {
+        ClientBuilder::new(meta)
+    }
+}
+
+/// Options of a [`Client`] besides where to connect.
+pub struct ClientBuilder {
+    meta: ConnectionMeta,
+    record: Option<std::path::PathBuf>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    retry: Option<RetryPolicy>,
+    rate_limit: Option<RateLimit>,
+}
+
+impl ClientBuilder {
+    pub fn new(meta: ConnectionMeta) -> Self {
+        Self {
+            meta,
+            record: None,
+            interceptors: Vec::new(),
+            retry: None,
+            rate_limit: None,
+        }
+    }
+
+    /// run every call through `interceptor`, after those added before, see
+    /// [`interceptor`](crate::interceptor).
+    pub fn interceptor<I: Interceptor>(mut self, interceptor: I) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// retry idempotent calls failing with a broken link, see [`RetryPolicy`].
+    ///
+    /// The retries happen after the interceptors, which see a single call.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// throttle the calls sent, see [`RateLimit`].
+    ///
+    /// Applies to every attempt of a retried call, not to the requests made
+    /// to reconcile after a reconnect.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// write every frame sent and received to a cassette at `path`, see
+    /// [`cassette`](crate::cassette). The secret token and options are redacted.
+    pub fn record<P: Into<std::path::PathBuf>>(mut self, path: P) -> Self {
+        self.record = Some(path.into());
+        self
+    }
+
+    pub async fn connect(self) -> Result<(Client, mpsc::UnboundedReceiver<Notification>)> {
+        let (inner, notify_rx) = ClientInner::connect(self).await?;
         let client = Client {
             inner: Arc::new(inner),
         };
@@ -100,32 +248,76 @@ impl Client {
 
 pub struct ClientInner {
     message_tx: mpsc::Sender<RPCRequest>,
-    token: Option<String>,
+    request_id: Arc<AtomicI64>,
+    token: SharedToken,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    close_tx: watch::Sender<bool>,
+    /// whether the background task finished without panicking, once it did
+    done: watch::Receiver<Option<bool>>,
     _drop_rx: oneshot::Receiver<()>,
 }
 
 impl ClientInner {
     async fn connect(
-        meta: ConnectionMeta,
+        builder: ClientBuilder,
     ) -> Result<(Self, mpsc::UnboundedReceiver<Notification>)> {
-        let (ws, _) = tokio_tungstenite::connect_async(&meta)
+        let ClientBuilder {
+            meta,
+            record,
+            mut interceptors,
+            retry,
+            rate_limit,
+        } = builder;
+        if let Some(policy) = retry {
+            interceptors.push(Arc::new(policy));
+        }
+        if let Some(limit) = rate_limit {
+            interceptors.push(Arc::new(Limiter::new(limit)));
+        }
+        let recorder = match record {
+            Some(path) => Some(Recorder::create(path).map_err(Error::Io)?),
+            None => None,
+        };
+        let span = tracing::info_span!("connection", url = %redact_url(&meta.url));
+        let ws = runtime::connect(&meta)
+            .instrument(span.clone())
             .await
             .map_err(Error::Connect)?;
+        span.in_scope(|| tracing::info!("connected"));
         let (message_tx, message_rx) = mpsc::channel(32);
+        let request_id = Arc::new(AtomicI64::new(1));
         let (notification_tx, notification_rx) = mpsc::unbounded_channel();
         let (drop_tx, _drop_rx) = oneshot::channel();
-        let token = meta.token.clone();
-        tokio::spawn(Self::background(
+        let (close_tx, close_rx) = watch::channel(false);
+        let (done_tx, done) = watch::channel(None);
+        let token = SharedToken::new(meta.token.clone());
+        let background = Self::background(
             ws,
             meta,
+            token.clone(),
+            request_id.clone(),
             message_rx,
             drop_tx,
+            close_rx,
             notification_tx,
-        ));
+            recorder,
+        )
+        .instrument(span);
+        runtime::spawn(async move {
+            let finished = AssertUnwindSafe(background).catch_unwind().await.is_ok();
+            if !finished {
+                tracing::error!("background task panicked");
+            }
+            done_tx.send_replace(Some(finished));
+        });
         Ok((
             Self {
                 message_tx,
+                request_id,
                 token,
+                interceptors,
+                close_tx,
+                done,
                 _drop_rx,
             },
             notification_rx,
@@ -133,17 +325,81 @@ impl ClientInner {
     }
 
     pub async fn call<C: Call>(&self, call: C) -> Result<C::Response> {
-        let (tx, rx) = oneshot::channel();
-
         let method = call.method();
-        let params = match call.to_params(self.token.as_ref().map(AsRef::as_ref)) {
-            Some(params) => Some(serde_json::to_value(params).map_err(Error::Encode)?),
-            None => None,
+        let span = tracing::debug_span!(
+            "rpc",
+            method = %method,
+            id = tracing::field::Empty,
+            gid = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        );
+        if let Some(gid) = call.gid() {
+            span.record("gid", tracing::field::display(gid));
+        }
+        // the leading token is put back when sent, it may be rotated by then
+        let needs_token = call.needs_token();
+        let token = self.token.get();
+        let (authenticated, params) = match call.to_params(token.as_ref().map(Secret::expose)) {
+            Some(params) => {
+                let authenticated = match token {
+                    Some(_) => params.token().is_some(),
+                    None => needs_token,
+                };
+                (authenticated, params.untokenized().map_err(Error::Encode)?)
+            }
+            None => (false, Vec::new()),
+        };
+        let request = Request {
+            method: method.clone().into_owned(),
+            params,
+            authenticated,
+            idempotent: C::IDEMPOTENT,
+            priority: C::PRIORITY,
+        };
+
+        async move {
+            let started = std::time::Instant::now();
+            let result = match Next::new(&self.interceptors, self).run(request).await {
+                Ok(value) => serde_json::from_value(value).map_err(Error::Decode),
+                Err(e) => Err(e),
+            };
+
+            let latency = started.elapsed();
+            tracing::Span::current().record("latency_ms", latency.as_millis() as u64);
+            if let Err(e) = &result {
+                tracing::warn!(method = %method, "call failed in {latency:?}: {e}");
+            }
+            result
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// send a request past the interceptors.
+    pub(crate) async fn send(&self, request: Request) -> Result<serde_json::Value> {
+        let Request {
+            method,
+            mut params,
+            authenticated,
+            ..
+        } = request;
+        if authenticated {
+            if let Some(token) = self.token.get() {
+                params.insert(0, serde_json::Value::String(token.expose().to_string()));
+            }
+        }
+        let params = match (authenticated, params.is_empty()) {
+            (false, true) => None,
+            _ => Some(serde_json::Value::Array(params)),
         };
-        
-        tracing::debug!("call method: {}, params: {:?}", method, params);
 
+        let id = self.request_id.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::Span::current().record("id", id);
+        tracing::debug!("call method: {}, params: {:?}", method, RedactedParams(&params));
+
+        let (tx, rx) = oneshot::channel();
         let request = RPCRequest {
+            id,
             params,
             method,
             handler: tx,
@@ -153,53 +409,136 @@ impl ClientInner {
             .await
             .map_err(|_| Error::ChannelSend)?;
         match rx.await.map_err(Error::ChannelRecv)? {
-            RPCReponse::Success(value) => {
-                serde_json::from_value(value).map_err(Error::Decode)
-            }
+            RPCReponse::Success(value) => Ok(value),
             RPCReponse::Error(err) => Err(err.into()),
         }
     }
 
+    /// call a method by name, see [`RawCall`].
+    pub async fn call_raw(
+        &self,
+        method: impl Into<String>,
+        params: Vec<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        self.call(RawCall::new(method, params)).await
+    }
+
+    /// switch to a new rpc secret without reconnecting.
+    ///
+    /// With `change_on_server`, the secret of aria2 is changed first through
+    /// `aria2.changeGlobalOption`, authenticated with the current one; calls
+    /// already in flight may fail as unauthorized. Otherwise the secret is
+    /// only swapped for later calls, for when aria2 was changed by other means.
+    pub async fn rotate_secret(&self, secret: impl Into<Secret>, change_on_server: bool) -> Result<()> {
+        let secret = secret.into();
+        if change_on_server {
+            self.call(ChangeGlobalOption::new(Aria2Options {
+                rpc_secret: Some(secret.clone()),
+                ..Default::default()
+            }))
+            .await?;
+        }
+        self.token
+            .set(Some(Secret::new(format!("token:{}", secret.expose()))));
+        Ok(())
+    }
+
+    /// stop the connection and wait for the background task to finish.
+    ///
+    /// Calls made before are still sent and their responses waited for, up to
+    /// 10 seconds, then the websocket is closed. Later calls fail with
+    /// [`Error::ChannelSend`]. Closing again, from any clone, waits as well.
+    pub async fn close(&self) -> Result<()> {
+        self.close_tx.send_replace(true);
+        let mut done = self.done.clone();
+        let finished = done.wait_for(Option::is_some).await.map(|done| *done);
+        match finished {
+            Ok(Some(true)) => Ok(()),
+            _ => Err(Error::BackgroundPanicked),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn background(
-        ws: WSStream,
+        ws: WsStream,
         meta: ConnectionMeta,
+        token: SharedToken,
+        request_id: Arc<AtomicI64>,
         mut message_rx: mpsc::Receiver<RPCRequest>,
         mut drop_tx: oneshot::Sender<()>,
+        mut close_rx: watch::Receiver<bool>,
         notification_tx: mpsc::UnboundedSender<Notification>,
+        mut recorder: Option<Recorder>,
     ) {
         let (mut ws_tx, mut ws_rx) = ws.split();
-        let mut shutdown = tokio::spawn({
+        let shutdown = {
             let notification_tx = notification_tx.clone();
             async move {
                 tokio::join!(drop_tx.closed(), notification_tx.closed());
             }
-        });
+        };
+        tokio::pin!(shutdown);
 
-        let mut request_id = 1i64;
-        let mut pending_requests = std::collections::HashMap::new();
+        let mut protocol = Protocol::new(request_id);
+        let (snapshot_tx, mut snapshot_rx) = mpsc::unbounded_channel();
+        // deadline of the calls still in flight, once closing
+        let mut closing: Option<Instant> = None;
 
         loop {
+            if let Err(e) = Self::request_snapshot(
+                &mut ws_tx,
+                &mut recorder,
+                &mut protocol,
+                token.get().as_ref().map(Secret::expose),
+                snapshot_tx.clone(),
+            )
+            .await
+            {
+                tracing::error!("request snapshot error: {e}");
+            }
             loop {
-                if notification_tx.is_closed() && message_rx.is_closed() {
+                if notification_tx.is_closed() && message_rx.is_closed() && closing.is_none() {
                     tracing::info!("background task shutdown");
                     return;
                 }
+                if let Some(deadline) = closing {
+                    let drained = message_rx.is_empty() && protocol.is_idle();
+                    if drained || Instant::now() >= deadline {
+                        if !drained {
+                            tracing::warn!("closing with calls still unanswered");
+                        }
+                        Self::close_socket(ws_tx, ws_rx, &mut recorder, &mut protocol).await;
+                        tracing::info!("background task closed");
+                        return;
+                    }
+                }
+                let drain_wait = closing.map(|deadline| deadline.saturating_duration_since(Instant::now()));
                 tokio::select! {
                     _ = &mut shutdown => {
                         tracing::info!("background task shutdown");
                         return;
                     }
+                    Ok(()) = close_rx.changed(), if closing.is_none() => {
+                        tracing::info!("closing");
+                        closing = Some(Instant::now() + CLOSE_TIMEOUT);
+                        message_rx.close();
+                    }
+                    _ = runtime::sleep(drain_wait.unwrap_or_default()), if drain_wait.is_some() => {}
                     Some(msg) = message_rx.recv() => {
-                        request_id += 1;
-                        pending_requests.insert(request_id, msg.handler);
-
-                        if let Err(e) = timeout(
-                            Duration::from_secs(10),
-                           Self::send_request(&mut ws_tx, request_id, msg.method, msg.params,)
-                        ).await {
-                            tracing::error!("send request error: {e}");
-                            break;
-                        }
+                        let (id, method) = (msg.id, msg.method.clone());
+                        let err = match protocol.request(msg) {
+                            Ok(text) => match timeout(
+                                Duration::from_secs(10),
+                                Self::send_frame(&mut ws_tx, &mut recorder, text),
+                            ).await {
+                                Ok(Ok(())) => continue,
+                                Ok(Err(e)) => e.to_string(),
+                                Err(e) => e.to_string(),
+                            },
+                            Err(e) => e.to_string(),
+                        };
+                        tracing::error!(method = %method, id, "send request error: {err}");
+                        break;
                     }
                     Some(msg) = ws_rx.next() => {
                         let text = match msg {
@@ -216,11 +555,29 @@ impl ClientInner {
                                 break;
                             }
                         };
-                        Self::handle_response(&text, &mut pending_requests, notification_tx.clone());
+                        if let Some(recorder) = recorder.as_mut() {
+                            recorder.record(Direction::Received, &text);
+                        }
+                        for n in protocol.receive(&text) {
+                            if notification_tx.send(n).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Some(snapshot) = snapshot_rx.recv() => {
+                        for n in protocol.reconcile(snapshot) {
+                            if notification_tx.send(n).is_err() {
+                                break;
+                            }
+                        }
                     }
                 }
             }
-            pending_requests.clear();
+            protocol.disconnected();
+            if closing.is_some() {
+                tracing::info!("background task closed");
+                return;
+            }
 
             // reconnect
             loop {
@@ -228,84 +585,172 @@ impl ClientInner {
                     tracing::info!("background task shutdown");
                     return;
                 }
-                match timeout(
-                    Duration::from_secs(10),
-                    tokio_tungstenite::connect_async(&meta),
-                )
-                .await
-                {
-                    Err(e) => {
-                        tracing::error!("reconnect error: {e}, will retry in 10 seconds");
-                        tokio::time::sleep(Duration::from_secs(10)).await;
+                let reconnect = async {
+                    match timeout(Duration::from_secs(10), runtime::connect(&meta)).await {
+                        Err(e) => {
+                            tracing::error!("reconnect timeout: {e}, will retry in 10 seconds");
+                            runtime::sleep(Duration::from_secs(10)).await;
+                            None
+                        }
+                        Ok(Err(e)) => {
+                            tracing::error!("reconnect error: {e}, will retry in 10 seconds");
+                            runtime::sleep(Duration::from_secs(10)).await;
+                            None
+                        }
+                        Ok(Ok(new_ws)) => Some(new_ws),
                     }
-                    Ok(Err(e)) => {
-                        tracing::error!("reconnect timeout: {e}, will retry in 10 seconds");
-                        tokio::time::sleep(Duration::from_secs(10)).await;
+                };
+                tokio::select! {
+                    new_ws = reconnect => {
+                        if let Some(new_ws) = new_ws {
+                            tracing::info!("reconnected");
+                            let (tx, rx) = new_ws.split();
+                            ws_tx = tx;
+                            ws_rx = rx;
+                            break;
+                        }
                     }
-                    Ok(Ok((new_ws, _))) => {
-                        let (tx, rx) = new_ws.split();
-                        ws_tx = tx;
-                        ws_rx = rx;
-                        break;
+                    // nothing in flight can be answered while disconnected
+                    Ok(()) = close_rx.changed() => {
+                        tracing::info!("background task closed");
+                        return;
                     }
                 }
             }
         }
     }
 
-    async fn send_request(
-        sink: &mut SplitSink<WSStream, WSMessage>,
-        id: i64,
-        method: &str,
-        params: Option<serde_json::Value>,
-    ) -> Result<()> {
-        let rpc_req = jsonrpc::Request {
-            id: Some(id),
-            jsonrpc: "2.0",
-            method,
-            params,
-        };
-        sink.send(WSMessage::Text(
-            serde_json::to_string(&rpc_req)
-                .map_err(Error::Encode)?
-                .into(),
-        ))
-        .await
-        .map_err(Error::Websocket)
-    }
-
-    fn handle_response(
-        text: &str,
-        pending_requests: &mut std::collections::HashMap<i64, oneshot::Sender<RPCReponse>>,
-        notification_tx: mpsc::UnboundedSender<Notification>,
+    /// send a close frame and wait for the one of aria2, briefly.
+    async fn close_socket(
+        mut sink: SplitSink<WsStream, WSMessage>,
+        mut stream: SplitStream<WsStream>,
+        recorder: &mut Option<Recorder>,
+        protocol: &mut Protocol,
     ) {
-        if let Ok(resp) = serde_json::from_str::<
-            jsonrpc::Response<i64, serde_json::Value, Vec<NotificationParam>>,
-        >(text)
-        {
-            match resp {
-                jsonrpc::Response::Err { id, error } => {
-                    if let Some(tx) = pending_requests.remove(&id) {
-                        let _ = tx.send(RPCReponse::Error(error));
+        let handshake = async {
+            sink.close().await?;
+            while let Some(msg) = stream.next().await {
+                if let WSMessage::Text(text) = msg? {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.record(Direction::Received, &text);
                     }
+                    protocol.receive(&text);
                 }
-                jsonrpc::Response::Resp { id, result } => {
-                    if let Some(tx) = pending_requests.remove(&id) {
-                        let _ = tx.send(RPCReponse::Success(result));
-                    }
-                }
-                jsonrpc::Response::Notification { method, params } => {
-                    tokio::spawn(async move {
-                        let method = method;
-                        for param in params {
-                            if notification_tx.send(Notification::new(&method, param.gid)).is_err()
-                            {
-                                break;
-                            }
-                        }
-                    });
-                }
             }
+            Ok::<_, tungstenite::Error>(())
+        };
+        match timeout(Duration::from_secs(5), handshake).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!("close error: {e}"),
+            Err(e) => tracing::warn!("close timeout: {e}"),
+        }
+    }
+
+    async fn send_frame(
+        sink: &mut SplitSink<WsStream, WSMessage>,
+        recorder: &mut Option<Recorder>,
+        text: String,
+    ) -> Result<()> {
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(Direction::Sent, &text);
+        }
+        sink.send(WSMessage::Text(text.into()))
+            .await
+            .map_err(Error::Websocket)
+    }
+
+    /// ask for the session info and the current state of all queues, the
+    /// result is delivered through `snapshot_tx` once all replies arrived.
+    async fn request_snapshot(
+        sink: &mut SplitSink<WsStream, WSMessage>,
+        recorder: &mut Option<Recorder>,
+        protocol: &mut Protocol,
+        token: Option<&str>,
+        snapshot_tx: mpsc::UnboundedSender<crate::protocol::Snapshot>,
+    ) -> Result<()> {
+        let (frames, pending) = protocol.snapshot(token).map_err(Error::Encode)?;
+        for text in frames {
+            timeout(
+                Duration::from_secs(10),
+                Self::send_frame(sink, recorder, text),
+            )
+            .await
+            .map_err(|_| Error::ChannelSend)??;
+        }
+
+        let collect = async move {
+            if let Some(snapshot) = pending.collect().await {
+                let _ = snapshot_tx.send(snapshot);
+            }
+        };
+        runtime::spawn(collect.in_current_span());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> (String, Option<String>) {
+        let meta: ConnectionMeta = s.parse().unwrap();
+        (meta.url, meta.token.map(|token| token.expose().to_string()))
+    }
+
+    fn url(s: &str) -> String {
+        parse(s).0
+    }
+
+    #[test]
+    fn shorthand() {
+        assert_eq!(url("localhost"), "ws://localhost:6800/jsonrpc");
+        assert_eq!(url("localhost:6801"), "ws://localhost:6801/jsonrpc");
+        assert_eq!(url(" 10.0.0.2:6800/ "), "ws://10.0.0.2:6800/jsonrpc");
+        assert_eq!(url("ws://host"), "ws://host:6800/jsonrpc");
+        assert_eq!(url("wss://host:443/rpc"), "wss://host:443/rpc");
+    }
+
+    #[test]
+    fn http_is_mapped_to_ws() {
+        assert_eq!(url("http://host:6800/jsonrpc"), "ws://host:6800/jsonrpc");
+        assert_eq!(url("HTTPS://host/jsonrpc"), "wss://host:6800/jsonrpc");
+        assert!("ftp://host".parse::<ConnectionMeta>().is_err());
+    }
+
+    #[test]
+    fn ipv6() {
+        assert_eq!(url("[::1]"), "ws://[::1]:6800/jsonrpc");
+        assert_eq!(url("ws://[fe80::1]:6801/jsonrpc"), "ws://[fe80::1]:6801/jsonrpc");
+    }
+
+    #[test]
+    fn invalid_port() {
+        for s in ["host:65536", "host:-1", "host:", "[::1]:port"] {
+            assert!(s.parse::<ConnectionMeta>().is_err(), "{s}");
+        }
+        assert!(":6800".parse::<ConnectionMeta>().is_err());
+    }
+
+    #[test]
+    fn query() {
+        assert_eq!(url("host?a=1"), "ws://host:6800/jsonrpc?a=1");
+        assert_eq!(url("ws://host:6800/jsonrpc?a=1#b"), "ws://host:6800/jsonrpc?a=1#b");
+    }
+
+    #[test]
+    fn secret() {
+        let (url, token) = parse("ws://token:a%2Fb%40c@host:6800/jsonrpc");
+        assert_eq!(url, "ws://host:6800/jsonrpc");
+        assert_eq!(token.as_deref(), Some("token:a/b@c"));
+        assert_eq!(parse("token:s@host").1.as_deref(), Some("token:s"));
+        assert!("ws://user:s@host".parse::<ConnectionMeta>().is_err());
+    }
+
+    #[test]
+    fn invalid_percent_encoding() {
+        assert_eq!(percent_decode("%e2%9C%93").unwrap(), "\u{2713}");
+        for s in ["%+1", "%-1", "%1", "%", "%zz", "%ff"] {
+            assert!(percent_decode(s).is_err(), "{s}");
         }
     }
 }
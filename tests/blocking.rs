@@ -0,0 +1,72 @@
+#![allow(clippy::result_large_err)]
+
+use std::time::Duration;
+
+use aria2_rs_yet::blocking::{Client, Notifications};
+use aria2_rs_yet::call::{AddUri, GetVersion};
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::Notification;
+use tokio::runtime::Runtime;
+
+/// aria2 runs on a runtime of its own, the client on its private one.
+fn start() -> (Runtime, FakeAria2, Client, Notifications) {
+    let runtime = Runtime::new().unwrap();
+    let server = runtime.block_on(FakeAria2::start()).unwrap();
+    let (client, notifications) = Client::connect(server.meta()).unwrap();
+    (runtime, server, client, notifications)
+}
+
+fn wait_until(condition: impl Fn() -> bool) {
+    for _ in 0..1000 {
+        if condition() {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    panic!("timed out");
+}
+
+#[test]
+fn call_and_notifications() {
+    let (_runtime, server, client, mut notifications) = start();
+    client.call(GetVersion).unwrap();
+    let gid = client
+        .call(AddUri::uris(vec!["http://example.com/a.iso"]))
+        .unwrap()
+        .0;
+    let started = notifications
+        .by_ref()
+        .find(|n| matches!(n, Notification::DownloadStart(g) if *g == gid));
+    assert!(started.is_some());
+
+    server.advance(Duration::from_secs(3600));
+    let completed = notifications
+        .by_ref()
+        .find(|n| matches!(n, Notification::DownloadComplete(g) if *g == gid));
+    assert!(completed.is_some());
+    assert!(notifications.try_next().is_none());
+}
+
+#[test]
+fn drop_closes_the_connection() {
+    let (_runtime, server, client, notifications) = start();
+    client.call(GetVersion).unwrap();
+    // kept alive by the notifications
+    let clone = client.clone();
+    drop(client);
+    clone.call(GetVersion).unwrap();
+    drop(clone);
+    drop(notifications);
+    wait_until(|| server.connections() == 0);
+}
+
+#[test]
+fn drop_within_a_runtime() {
+    let (runtime, server, client, notifications) = start();
+    client.call(GetVersion).unwrap();
+    runtime.block_on(async move {
+        drop(client);
+        drop(notifications);
+    });
+    wait_until(|| server.connections() == 0);
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::time::Duration;
+
+use aria2_rs_yet::call::{AddUri, GetVersion, TaskStatus, TellStatus};
+use aria2_rs_yet::testkit::{FakeAria2, ReplayServer};
+use aria2_rs_yet::{Client, Error, Notification};
+use common::next_matching;
+use serde_json::json;
+
+#[tokio::test]
+async fn rotation_is_redacted() {
+    let server = FakeAria2::builder().secret("OLDSECRET").start().await.unwrap();
+    let path = std::env::temp_dir().join(format!(
+        "aria2-rs-yet-rotation-{}.jsonl",
+        std::process::id()
+    ));
+    let (client, _rx) = Client::builder(server.meta())
+        .record(&path)
+        .connect()
+        .await
+        .unwrap();
+    client.rotate_secret("SUPERSECRETNEW", true).await.unwrap();
+    client.call(GetVersion).await.unwrap();
+    client.close().await.unwrap();
+
+    let cassette = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(cassette.contains("aria2.changeGlobalOption"));
+    assert!(cassette.contains("aria2.getVersion"));
+    assert!(!cassette.contains("SUPERSECRETNEW"));
+    assert!(!cassette.contains("OLDSECRET"));
+}
+
+#[tokio::test]
+async fn replay_answers_recorded_calls() {
+    let server = FakeAria2::builder().secret("SECRET").start().await.unwrap();
+    let path = std::env::temp_dir().join(format!(
+        "aria2-rs-yet-replay-{}.jsonl",
+        std::process::id()
+    ));
+    let (client, mut rx) = Client::builder(server.meta())
+        .record(&path)
+        .connect()
+        .await
+        .unwrap();
+    let mut recorded = vec![];
+    for uri in ["http://example.com/a.iso", "http://example.com/b.iso"] {
+        let gid = client.call(AddUri::uris(vec![uri])).await.unwrap().0;
+        next_matching(&mut rx, |n| matches!(n, Notification::DownloadStart(g) if *g == gid)).await;
+        recorded.push(gid);
+    }
+    server.advance(Duration::from_secs(3600));
+    for gid in &recorded {
+        next_matching(&mut rx, |n| matches!(n, Notification::DownloadComplete(g) if g == gid))
+            .await;
+    }
+    for gid in &recorded {
+        client.call(TellStatus::new(gid.clone())).await.unwrap();
+    }
+    client.close().await.unwrap();
+
+    let replay = ReplayServer::start(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let (client, mut rx) = Client::builder(replay.meta()).connect().await.unwrap();
+    let mut replayed = vec![];
+    for uri in ["http://example.com/a.iso", "http://example.com/b.iso"] {
+        replayed.push(client.call(AddUri::uris(vec![uri])).await.unwrap().0);
+    }
+    assert_eq!(replayed, recorded);
+
+    // in the order they were recorded, each after the response it followed
+    let mut notifications = vec![];
+    while notifications.len() < 4 {
+        let notification = next_matching(&mut rx, |n| {
+            matches!(n, Notification::DownloadStart(_) | Notification::DownloadComplete(_))
+        })
+        .await;
+        notifications.push(match notification {
+            Notification::DownloadStart(gid) => ("start", gid),
+            Notification::DownloadComplete(gid) => ("complete", gid),
+            _ => unreachable!(),
+        });
+    }
+    assert_eq!(
+        notifications,
+        vec![
+            ("start", recorded[0].clone()),
+            ("start", recorded[1].clone()),
+            ("complete", recorded[0].clone()),
+            ("complete", recorded[1].clone()),
+        ]
+    );
+
+    // matched on params, not on the order they were recorded in
+    for gid in recorded.iter().rev() {
+        let status = client.call(TellStatus::new(gid.clone())).await.unwrap();
+        assert_eq!(status.gid.as_ref(), Some(gid));
+        assert_eq!(status.status, Some(TaskStatus::Complete));
+    }
+    assert!(replay.unmatched().is_empty());
+
+    let err = client
+        .call(AddUri::uris(vec!["http://example.com/c.iso"]))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Rpc(_)), "{err:?}");
+    assert_eq!(
+        replay.unmatched(),
+        vec![(
+            "aria2.addUri".to_string(),
+            vec![json!(["http://example.com/c.iso"])]
+        )]
+    );
+    client.close().await.unwrap();
+}
@@ -0,0 +1,118 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+use aria2_rs_yet::call::{AddUri, Aria2Params, Call};
+use aria2_rs_yet::interceptor::{BoxFuture, Interceptor, Next, Request};
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::{Client, Error, Result, RpcErrorKind};
+use serde_json::{json, Value};
+
+/// `aria2.getVersion`, keeping the token it is given.
+struct SeeToken(Arc<Mutex<Option<String>>>);
+
+impl Call for SeeToken {
+    type Response = serde_json::Value;
+
+    fn method(&self) -> Cow<'static, str> {
+        "aria2.getVersion".into()
+    }
+
+    fn to_params(self, token: Option<&str>) -> Option<Aria2Params<'_, Self>> {
+        *self.0.lock().unwrap() = token.map(String::from);
+        Some(Aria2Params::new(token, self))
+    }
+}
+
+#[tokio::test]
+async fn to_params_gets_the_current_token() {
+    let server = FakeAria2::builder().secret("OLD").start().await.unwrap();
+    let (client, _rx) = Client::connect(server.meta()).await.unwrap();
+    let seen = Arc::new(Mutex::new(None));
+
+    client.call(SeeToken(seen.clone())).await.unwrap();
+    assert_eq!(seen.lock().unwrap().as_deref(), Some("token:OLD"));
+
+    client.rotate_secret("NEW", true).await.unwrap();
+    client.call(SeeToken(seen.clone())).await.unwrap();
+    assert_eq!(seen.lock().unwrap().as_deref(), Some("token:NEW"));
+}
+
+/// `aria2.getVersion`, leaving out the token it is given.
+struct WithoutToken;
+
+impl Call for WithoutToken {
+    type Response = serde_json::Value;
+
+    fn method(&self) -> Cow<'static, str> {
+        "aria2.getVersion".into()
+    }
+
+    fn to_params(self, _: Option<&str>) -> Option<Aria2Params<'_, Self>> {
+        Some(Aria2Params::new(None, self))
+    }
+}
+
+/// Keeps the requests it sees.
+#[derive(Clone, Default)]
+struct Seen(Arc<Mutex<Vec<Request>>>);
+
+impl Interceptor for Seen {
+    fn intercept<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+        self.0.lock().unwrap().push(request.clone());
+        next.run(request)
+    }
+}
+
+#[tokio::test]
+async fn token_left_out_is_not_sent() {
+    let server = FakeAria2::builder().secret("S").start().await.unwrap();
+    let seen = Seen::default();
+    let (client, _rx) = Client::builder(server.meta())
+        .interceptor(seen.clone())
+        .connect()
+        .await
+        .unwrap();
+
+    let result = client.call(WithoutToken).await;
+    assert!(matches!(result, Err(Error::Rpc(e)) if e.kind() == RpcErrorKind::Unauthorized));
+    assert!(!seen.0.lock().unwrap()[0].authenticated);
+}
+
+/// Sends `aria2.getVersion` as `aria2.getSessionInfo`, and gives new
+/// downloads a directory.
+struct Rewrite;
+
+impl Interceptor for Rewrite {
+    fn intercept<'a>(&'a self, mut request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+        match request.method.as_str() {
+            "aria2.getVersion" => request.method = "aria2.getSessionInfo".into(),
+            "aria2.addUri" if request.params.len() == 1 => request.params.push(json!({"dir": "/injected"})),
+            _ => {}
+        }
+        next.run(request)
+    }
+}
+
+#[tokio::test]
+async fn interceptor_rewrites_requests() {
+    let server = FakeAria2::start().await.unwrap();
+    let seen = Seen::default();
+    let (client, _rx) = Client::builder(server.meta())
+        .interceptor(Rewrite)
+        .interceptor(seen.clone())
+        .connect()
+        .await
+        .unwrap();
+
+    let reply = client.call_raw("aria2.getVersion", vec![]).await.unwrap();
+    assert!(reply["sessionId"].is_string(), "{reply}");
+
+    let gid = client.call(AddUri::uris(vec!["http://example.com/a.iso"])).await.unwrap().0;
+    assert_eq!(server.status(&gid).unwrap()["dir"], "/injected");
+
+    // the interceptors after see the rewritten request
+    let seen = seen.0.lock().unwrap();
+    let methods: Vec<_> = seen.iter().map(|r| r.method.as_str()).collect();
+    assert_eq!(methods, ["aria2.getSessionInfo", "aria2.addUri"]);
+    assert_eq!(seen[1].params[1], json!({"dir": "/injected"}));
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use std::time::Duration;
+
+use aria2_rs_yet::call::GetVersion;
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::{Client, Error};
+
+use common::{connect, wait_received};
+
+#[tokio::test]
+async fn close_waits_for_calls_in_flight() {
+    let server = FakeAria2::start().await.unwrap();
+    let (client, _rx) = connect(Client::builder(server.meta())).await;
+    server.set_reply_delay(Duration::from_millis(200));
+
+    let close = async {
+        wait_received(&server, "aria2.getVersion", 0).await;
+        client.close().await
+    };
+    let (version, closed) = tokio::join!(client.call(GetVersion), close);
+    assert_eq!(version.unwrap().version, "1.37.0");
+    closed.unwrap();
+
+    assert!(matches!(client.call(GetVersion).await, Err(Error::ChannelSend)));
+}
@@ -0,0 +1,58 @@
+//! Helpers of the tests against [`FakeAria2`].
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use aria2_rs_yet::call::{AddUri, GetSessionInfo};
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::{Client, ClientBuilder, Gid, Notification};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// the next notification `matches` accepts, skipping the others.
+pub async fn next_matching(
+    rx: &mut UnboundedReceiver<Notification>,
+    matches: impl Fn(&Notification) -> bool,
+) -> Notification {
+    let next = async {
+        loop {
+            let notification = rx.recv().await.expect("notifications closed");
+            if matches(&notification) {
+                return notification;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), next)
+        .await
+        .expect("no notification in time")
+}
+
+/// connect once the snapshot taken on connecting is answered, so that it is
+/// not held back by a reply delay set later.
+pub async fn connect(builder: ClientBuilder) -> (Client, UnboundedReceiver<Notification>) {
+    let (client, rx) = builder.connect().await.unwrap();
+    // answered in order, after the snapshot
+    client.call(GetSessionInfo).await.unwrap();
+    (client, rx)
+}
+
+pub fn received(server: &FakeAria2, method: &str) -> usize {
+    server.received().iter().filter(|m| *m == method).count()
+}
+
+/// wait until aria2 got `method` more than `count` times.
+pub async fn wait_received(server: &FakeAria2, method: &str, count: usize) {
+    while received(server, method) <= count {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+/// add a download and wait for it to start.
+pub async fn start_download(client: &Client, rx: &mut UnboundedReceiver<Notification>) -> Gid {
+    let gid = client
+        .call(AddUri::uris(vec!["http://example.com/a.iso"]))
+        .await
+        .unwrap()
+        .0;
+    next_matching(rx, |n| matches!(n, Notification::DownloadStart(g) if *g == gid)).await;
+    gid
+}
@@ -0,0 +1,85 @@
+use aria2_rs_yet::call::{AddUri, Aria2Call, Call};
+use aria2_rs_yet::limit::Priority;
+use aria2_rs_yet::options::Aria2Options;
+use aria2_rs_yet::Gid;
+use serde_json::{json, Value};
+
+const GID: &str = "2089b05ecca3d829";
+
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.test", response = String, idempotent, bulk)]
+struct Full {
+    #[aria2(gid)]
+    gid: Gid,
+    #[aria2(skip)]
+    #[allow(dead_code)]
+    note: &'static str,
+    options: Option<Aria2Options>,
+    position: Option<i32>,
+}
+
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "system.test", response = Vec<String>, no_token)]
+struct NoToken(String);
+
+#[derive(Debug, Aria2Call)]
+#[aria2(method = "aria2.plain", response = String)]
+struct Plain;
+
+fn full(options: Option<Aria2Options>, position: Option<i32>) -> Full {
+    Full {
+        gid: GID.parse().unwrap(),
+        note: "not sent",
+        options,
+        position,
+    }
+}
+
+fn params<C: Call>(call: C) -> Value {
+    serde_json::to_value(call.to_params(Some("token:t"))).unwrap()
+}
+
+#[test]
+fn container_attributes() {
+    let call = full(None, None);
+    assert_eq!(call.method(), "aria2.test");
+    assert_eq!(call.gid().map(Gid::as_str), Some(GID));
+    const { assert!(Full::IDEMPOTENT) };
+    assert_eq!(Full::PRIORITY, Priority::Bulk);
+
+    assert_eq!(Plain.method(), "aria2.plain");
+    assert!(Plain.gid().is_none());
+    const { assert!(!Plain::IDEMPOTENT) };
+    assert_eq!(Plain::PRIORITY, Priority::Interactive);
+}
+
+#[test]
+fn token_and_skip() {
+    assert_eq!(params(full(None, None)), json!(["token:t", GID]));
+    assert_eq!(params(NoToken("a".into())), json!(["a"]));
+    assert_eq!(params(Plain), json!(["token:t"]));
+    assert!(full(None, None).needs_token());
+    assert!(!NoToken("a".into()).needs_token());
+}
+
+#[test]
+fn trailing_none_is_left_out() {
+    let options = Aria2Options {
+        dir: Some("/d".into()),
+        ..Default::default()
+    };
+    assert_eq!(params(full(Some(options), None)), json!(["token:t", GID, {"dir": "/d"}]));
+}
+
+#[test]
+fn none_before_some_is_a_placeholder() {
+    assert_eq!(params(full(None, Some(0))), json!(["token:t", GID, {}, 0]));
+    let call = AddUri::uris(vec!["http://a/x"]).position(Some(0));
+    assert_eq!(params(call), json!(["token:t", ["http://a/x"], {}, 0]));
+}
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use aria2_rs_yet::call::{AddUri, RawCall};
+use aria2_rs_yet::input_file;
+use aria2_rs_yet::options::Aria2Options;
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::Client;
+use common::connect;
+use serde_json::json;
+
+#[tokio::test]
+async fn exported_session_is_added_back() {
+    let server = FakeAria2::builder()
+        .max_concurrent_downloads(1)
+        .start()
+        .await
+        .unwrap();
+    let (client, _rx) = connect(Client::builder(server.meta())).await;
+    let mut gids = vec![];
+    for (uris, dir) in [
+        (vec!["http://a/x.iso", "http://b/x.iso"], "/downloads"),
+        (vec!["http://c/y.zip"], "/other"),
+        (vec!["http://d/z.tar"], "/paused"),
+    ] {
+        let options = Aria2Options {
+            dir: Some(dir.to_string()),
+            ..Default::default()
+        };
+        let add = AddUri::uris(uris).options(Some(options));
+        gids.push(client.call(add).await.unwrap().0);
+    }
+    let pause = RawCall::new("aria2.pause", vec![json!(gids[2])]);
+    client.call(pause).await.unwrap();
+
+    let path = std::env::temp_dir().join(format!(
+        "aria2-rs-yet-session-{}.txt",
+        std::process::id()
+    ));
+    let session = client.export_session().await.unwrap();
+    input_file::write(std::fs::File::create(&path).unwrap(), &session).unwrap();
+    let read = input_file::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(read.len(), 3);
+    assert_eq!(read[0].uris, ["http://a/x.iso", "http://b/x.iso"]);
+    assert_eq!(read[1].uris, ["http://c/y.zip"]);
+    for (download, (gid, dir)) in read.iter().zip(gids.iter().zip(["/downloads", "/other", "/paused"])) {
+        let options = download.options.as_ref().unwrap();
+        assert_eq!(options.gid.as_ref(), Some(gid));
+        assert_eq!(options.dir.as_deref(), Some(dir));
+    }
+    assert_eq!(read[2].options.as_ref().unwrap().extra["pause"], "true");
+    assert!(!read[0].options.as_ref().unwrap().extra.contains_key("pause"));
+
+    // the same downloads, under the same gids, on another instance
+    let other = FakeAria2::start().await.unwrap();
+    let (client, _rx) = connect(Client::builder(other.meta())).await;
+    for download in read {
+        let gid = download.options.as_ref().unwrap().gid.clone().unwrap();
+        assert_eq!(client.call(download).await.unwrap().0, gid);
+    }
+}
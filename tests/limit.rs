@@ -0,0 +1,58 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use aria2_rs_yet::call::{AddUri, GetVersion, TellActive};
+use aria2_rs_yet::limit::RateLimit;
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::Client;
+
+use common::{connect, wait_received};
+
+#[tokio::test]
+async fn one_call_in_flight_at_a_time() {
+    let server = FakeAria2::start().await.unwrap();
+    let limit = RateLimit::new().max_in_flight(1);
+    let (client, _rx) = connect(Client::builder(server.meta()).rate_limit(limit)).await;
+    server.set_reply_delay(Duration::from_millis(100));
+
+    let started = Instant::now();
+    let (a, b, c) = tokio::join!(
+        client.call(GetVersion),
+        client.call(GetVersion),
+        client.call(GetVersion),
+    );
+    a.unwrap();
+    b.unwrap();
+    c.unwrap();
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(300), "{elapsed:?}");
+}
+
+#[tokio::test]
+async fn interactive_calls_are_sent_before_bulk_ones() {
+    let server = FakeAria2::start().await.unwrap();
+    let limit = RateLimit::new().max_in_flight(1);
+    let (client, _rx) = connect(Client::builder(server.meta()).rate_limit(limit)).await;
+    server.set_reply_delay(Duration::from_millis(100));
+
+    let queued = async {
+        // both wait for the slot taken by getVersion
+        wait_received(&server, "aria2.getVersion", 0).await;
+        let add = client.call(AddUri::uris(vec!["http://example.com/a.iso"]));
+        let tell = client.call(TellActive::new());
+        tokio::join!(add, tell)
+    };
+    let (version, (add, tell)) = tokio::join!(client.call(GetVersion), queued);
+    version.unwrap();
+    add.unwrap();
+    tell.unwrap();
+
+    let received = server.received();
+    let sent: Vec<_> = received
+        .iter()
+        .skip_while(|m| *m != "aria2.getVersion")
+        .map(String::as_str)
+        .collect();
+    assert_eq!(sent, ["aria2.getVersion", "aria2.tellActive", "aria2.addUri"]);
+}
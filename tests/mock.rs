@@ -0,0 +1,75 @@
+use aria2_rs_yet::call::{AddUri, GetUris, GetVersion};
+use aria2_rs_yet::mock::{MockClient, RecordedCall};
+use aria2_rs_yet::{Aria2Api, Error, Gid, Notification, RpcError};
+use serde_json::json;
+
+const GID: &str = "2089b05ecca3d829";
+
+#[tokio::test]
+async fn type_stubs_go_before_method_stubs() {
+    let (client, _rx) = MockClient::new();
+    client.reply_method("aria2.addUri", "0000000000000001");
+    client.reply::<AddUri>(GID);
+
+    let first = client.call(AddUri::uris(vec!["http://a/x"])).await.unwrap();
+    assert_eq!(first.0, GID.parse::<Gid>().unwrap());
+    let second = client.call(AddUri::uris(vec!["http://a/y"])).await.unwrap();
+    assert_eq!(second.0, "0000000000000001".parse::<Gid>().unwrap());
+}
+
+#[tokio::test]
+async fn calls_are_recorded_without_the_token() {
+    let (client, _rx) = MockClient::new();
+    client.reply::<AddUri>(GID).reply::<GetUris>(json!([]));
+    client.reply_method("aria2.getGlobalStat", json!({}));
+
+    client.call(AddUri::uris(vec!["http://a/x"])).await.unwrap();
+    client.call(GetUris::new(GID.parse::<Gid>().unwrap())).await.unwrap();
+    client.call_raw("aria2.getGlobalStat", vec![]).await.unwrap();
+
+    assert_eq!(
+        client.calls(),
+        vec![
+            RecordedCall {
+                method: "aria2.addUri".to_string(),
+                params: Some(json!([["http://a/x"]])),
+            },
+            RecordedCall {
+                method: "aria2.getUris".to_string(),
+                params: Some(json!([GID])),
+            },
+            RecordedCall {
+                method: "aria2.getGlobalStat".to_string(),
+                params: Some(json!([])),
+            },
+        ]
+    );
+    assert_eq!(client.calls_to("aria2.getUris").len(), 1);
+}
+
+#[tokio::test]
+async fn unstubbed_and_failed_calls_are_errors() {
+    let (client, _rx) = MockClient::new();
+    let err = client.call(GetVersion).await.unwrap_err();
+    assert!(matches!(err, Error::Rpc(ref e) if e.code == 1), "{err:?}");
+
+    client.fail::<GetVersion>(RpcError {
+        code: 1,
+        message: "Unauthorized".to_string(),
+    });
+    let err = client.call(GetVersion).await.unwrap_err();
+    assert!(matches!(err, Error::Rpc(ref e) if e.message == "Unauthorized"), "{err:?}");
+    // each response is served once
+    assert!(client.call(GetVersion).await.is_err());
+    assert_eq!(client.calls_to("aria2.getVersion").len(), 3);
+}
+
+#[tokio::test]
+async fn notifications_are_delivered() {
+    let (client, mut rx) = MockClient::new();
+    let gid: Gid = GID.parse().unwrap();
+    client.notify(Notification::DownloadStart(gid.clone()));
+    client.notify(Notification::DownloadComplete(gid.clone()));
+    assert!(matches!(rx.recv().await, Some(Notification::DownloadStart(g)) if g == gid));
+    assert!(matches!(rx.recv().await, Some(Notification::DownloadComplete(g)) if g == gid));
+}
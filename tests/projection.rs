@@ -0,0 +1,61 @@
+mod common;
+
+use std::time::Duration;
+
+use aria2_rs_yet::call::{TaskStatus, TellActive, TellStatus, TellStatusField};
+use aria2_rs_yet::projection::{Projectable, Projection};
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::{Client, DownloadErrorCode, Gid};
+use common::start_download;
+
+#[derive(Debug, Projection)]
+struct Progress {
+    gid: Gid,
+    status: TaskStatus,
+    total_length: u64,
+    completed_length: u64,
+    error_code: Option<DownloadErrorCode>,
+}
+
+#[test]
+fn keys_are_the_fields() {
+    assert_eq!(
+        Progress::KEYS,
+        [
+            TellStatusField::Gid,
+            TellStatusField::Status,
+            TellStatusField::TotalLength,
+            TellStatusField::CompletedLength,
+            TellStatusField::ErrorCode,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn projected_reply_is_decoded() {
+    let server = FakeAria2::builder()
+        .file_size(1000)
+        .speed(100)
+        .start()
+        .await
+        .unwrap();
+    let (client, mut rx) = Client::builder(server.meta()).connect().await.unwrap();
+    let gid = start_download(&client, &mut rx).await;
+    server.advance(Duration::from_secs(2));
+
+    let progress: Progress = client.call(TellStatus::new(gid.clone()).project()).await.unwrap();
+    assert_eq!(progress.gid, gid);
+    assert_eq!(progress.status, TaskStatus::Active);
+    assert_eq!(progress.total_length, 1000);
+    assert_eq!(progress.completed_length, 200);
+    assert!(progress.error_code.is_none());
+
+    let active: Vec<Progress> = client.call(TellActive::new().project()).await.unwrap();
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].gid, gid);
+
+    server.fail_download(&gid, DownloadErrorCode::Timeout);
+    let progress: Progress = client.call(TellStatus::new(gid.clone()).project()).await.unwrap();
+    assert_eq!(progress.status, TaskStatus::Error);
+    assert_eq!(progress.error_code, Some(DownloadErrorCode::Timeout));
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::time::Duration;
+
+use aria2_rs_yet::call::AddUri;
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::{Client, Gid, Notification};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use common::{next_matching, start_download};
+
+/// complete the download of `gid` while no connection is there to be
+/// notified, and wait for the client to make up for it.
+async fn complete_while_disconnected(
+    server: &FakeAria2,
+    rx: &mut UnboundedReceiver<Notification>,
+    gid: &Gid,
+) {
+    server.drop_connections();
+    server.advance(Duration::from_secs(60));
+
+    let notification = next_matching(rx, |n| n.is_reconciled() && n.gid() == Some(gid)).await;
+    match notification {
+        Notification::Reconciled(inner) => {
+            assert!(matches!(*inner, Notification::DownloadComplete(_)), "{inner:?}");
+        }
+        n => panic!("{n:?}"),
+    }
+}
+
+#[tokio::test]
+async fn missed_completion_is_reconciled() {
+    let server = FakeAria2::start().await.unwrap();
+    let (client, mut rx) = Client::connect(server.meta()).await.unwrap();
+    let gid = start_download(&client, &mut rx).await;
+
+    complete_while_disconnected(&server, &mut rx, &gid).await;
+}
+
+#[tokio::test]
+async fn newest_stopped_are_reconciled() {
+    // more stopped downloads than a snapshot lists, 1000
+    let server = FakeAria2::builder()
+        .max_concurrent_downloads(1001)
+        .file_size(1)
+        .start()
+        .await
+        .unwrap();
+    let (client, mut rx) = Client::connect(server.meta()).await.unwrap();
+    let adds = (0..1000).map(|i| client.call(AddUri::uris(vec![format!("http://example.com/{i}")])));
+    for gid in futures_util::future::join_all(adds).await {
+        gid.unwrap();
+    }
+    server.advance(Duration::from_secs(1));
+    let gid = start_download(&client, &mut rx).await;
+
+    complete_while_disconnected(&server, &mut rx, &gid).await;
+}
+
+#[tokio::test]
+async fn restart_is_detected() {
+    let server = FakeAria2::start().await.unwrap();
+    let (client, mut rx) = Client::connect(server.meta()).await.unwrap();
+    start_download(&client, &mut rx).await;
+
+    server.restart();
+
+    let notification = next_matching(&mut rx, |n| matches!(n, Notification::ServerRestarted(_))).await;
+    match notification {
+        Notification::ServerRestarted(session_id) => assert_eq!(session_id, format!("{:040x}", 2)),
+        n => panic!("{n:?}"),
+    }
+}
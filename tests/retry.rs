@@ -0,0 +1,38 @@
+mod common;
+
+use std::time::Duration;
+
+use aria2_rs_yet::call::{AddUri, Call, GetVersion};
+use aria2_rs_yet::retry::RetryPolicy;
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::{Client, Error};
+
+use common::{connect, received, wait_received};
+
+/// make `call` and break the link once aria2 got it, before it answers.
+async fn break_link_during<C: Call>(server: &FakeAria2, client: &Client, call: C) -> aria2_rs_yet::Result<C::Response> {
+    let method = call.method().into_owned();
+    let count = received(server, &method);
+    server.set_reply_delay(Duration::from_secs(1));
+    let break_link = async {
+        wait_received(server, &method, count).await;
+        server.set_reply_delay(Duration::ZERO);
+        server.drop_connections();
+    };
+    tokio::join!(client.call(call), break_link).0
+}
+
+#[tokio::test]
+async fn only_idempotent_calls_are_retried() {
+    let server = FakeAria2::start().await.unwrap();
+    let policy = RetryPolicy::new().backoff(Duration::from_millis(10), Duration::from_millis(10), 1);
+    let (client, _rx) = connect(Client::builder(server.meta()).retry(policy)).await;
+
+    let version = break_link_during(&server, &client, GetVersion).await.unwrap();
+    assert_eq!(version.version, "1.37.0");
+    assert_eq!(received(&server, "aria2.getVersion"), 2);
+
+    let result = break_link_during(&server, &client, AddUri::uris(vec!["http://example.com/a.iso"])).await;
+    assert!(result.as_ref().is_err_and(Error::is_transient), "{:?}", result.map(|gid| gid.0));
+    assert_eq!(received(&server, "aria2.addUri"), 1);
+}
@@ -0,0 +1,62 @@
+//! The client on smol, against a fake aria2 on a tokio runtime of its own.
+//!
+//! Skipped when tokio is enabled, which takes precedence, run with
+//! `cargo test --no-default-features --features smol-runtime,testkit`.
+#![cfg(not(feature = "tokio-runtime"))]
+
+use std::time::Duration;
+
+use aria2_rs_yet::call::{AddUri, GetVersion};
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::{Client, Notification};
+
+/// a fake aria2 served by the returned runtime, to keep as long as needed.
+fn start() -> (tokio::runtime::Runtime, FakeAria2) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+    let server = runtime.block_on(FakeAria2::start()).unwrap();
+    (runtime, server)
+}
+
+#[test]
+fn calls_and_notifications() {
+    let (_runtime, server) = start();
+    smol::block_on(async {
+        let (client, mut rx) = Client::connect(server.meta()).await.unwrap();
+        assert_eq!(client.call(GetVersion).await.unwrap().version, "1.37.0");
+
+        let gid = client
+            .call(AddUri::uris(vec!["http://example.com/a.iso"]))
+            .await
+            .unwrap()
+            .0;
+        let notification = rx.recv().await.unwrap();
+        assert!(matches!(&notification, Notification::DownloadStart(g) if *g == gid), "{notification:?}");
+
+        client.close().await.unwrap();
+    });
+}
+
+#[test]
+fn reconnects() {
+    let (_runtime, server) = start();
+    let sessions = || server.received().iter().filter(|m| *m == "aria2.getSessionInfo").count();
+    smol::block_on(async {
+        let (client, _rx) = Client::connect(server.meta()).await.unwrap();
+        client.call(GetVersion).await.unwrap();
+
+        server.drop_connections();
+        // the snapshot taken on reconnecting asks for the session again
+        for _ in 0..1000 {
+            if sessions() == 2 {
+                break;
+            }
+            smol::Timer::after(Duration::from_millis(5)).await;
+        }
+        assert_eq!(sessions(), 2);
+        assert_eq!(client.call(GetVersion).await.unwrap().version, "1.37.0");
+    });
+}
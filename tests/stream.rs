@@ -0,0 +1,102 @@
+mod common;
+
+use aria2_rs_yet::call::{AddUri, RawCall, TellStatusField, TellStatusReply};
+use aria2_rs_yet::stream::Pagination;
+use aria2_rs_yet::testkit::FakeAria2;
+use aria2_rs_yet::{Client, Gid, Result};
+use common::{connect, received};
+use futures_util::{Stream, StreamExt};
+use serde_json::json;
+
+/// a fake aria2 with one active download and `waiting` queued behind it,
+/// returned in queue order.
+async fn queue(waiting: usize) -> (FakeAria2, Client, Vec<Gid>) {
+    let server = FakeAria2::builder()
+        .max_concurrent_downloads(1)
+        .start()
+        .await
+        .unwrap();
+    let (client, _rx) = connect(Client::builder(server.meta())).await;
+    let mut gids = vec![];
+    for n in 0..=waiting {
+        let uri = format!("http://example.com/{n}.iso");
+        gids.push(client.call(AddUri::uris(vec![uri])).await.unwrap().0);
+    }
+    gids.remove(0);
+    (server, client, gids)
+}
+
+/// the gids of the next `n` replies, fewer if the stream ends.
+async fn take<S>(stream: &mut S, n: usize) -> Vec<Gid>
+where
+    S: Stream<Item = Result<TellStatusReply>> + Unpin,
+{
+    let mut gids = vec![];
+    while gids.len() < n {
+        match stream.next().await {
+            Some(reply) => gids.push(reply.unwrap().gid.unwrap()),
+            None => break,
+        }
+    }
+    gids
+}
+
+fn pages(page_size: i32, overlap: i32) -> Pagination {
+    Pagination::new().page_size(page_size).overlap(overlap)
+}
+
+#[tokio::test]
+async fn pages_forward() {
+    let (server, client, waiting) = queue(7).await;
+    let before = received(&server, "aria2.tellWaiting");
+    let stream = client.waiting_stream_with(Some([TellStatusField::Status]), pages(3, 1));
+    let mut stream = Box::pin(stream);
+    assert_eq!(take(&mut stream, usize::MAX).await, waiting);
+    // 0..3, 2..6 then 5..7, short of the 4 asked for
+    assert_eq!(received(&server, "aria2.tellWaiting") - before, 3);
+}
+
+#[tokio::test]
+async fn pages_in_reverse() {
+    let (_server, client, mut waiting) = queue(7).await;
+    let stream = client.waiting_stream_with(Some([TellStatusField::Status]), pages(3, 1).reverse(true));
+    let mut stream = Box::pin(stream);
+    waiting.reverse();
+    assert_eq!(take(&mut stream, usize::MAX).await, waiting);
+}
+
+#[tokio::test]
+async fn removal_before_the_cursor_is_covered_by_the_overlap() {
+    for (overlap, skipped) in [(1, false), (0, true)] {
+        let (_server, client, waiting) = queue(7).await;
+        let stream = client.waiting_stream_with(Some([TellStatusField::Status]), pages(3, overlap));
+        let mut stream = Box::pin(stream);
+        let mut read = take(&mut stream, 3).await;
+        assert_eq!(read, waiting[..3]);
+
+        let remove = RawCall::new("aria2.remove", vec![json!(waiting[0])]);
+        client.call(remove).await.unwrap();
+        read.extend(take(&mut stream, usize::MAX).await);
+        if skipped {
+            // the entry moved to the cursor is missed
+            assert_eq!(read.len(), waiting.len() - 1);
+            assert!(!read.contains(&waiting[3]));
+        } else {
+            assert_eq!(read, waiting);
+        }
+    }
+}
+
+#[tokio::test]
+async fn insertion_before_the_cursor_is_not_repeated() {
+    let (_server, client, waiting) = queue(7).await;
+    let stream = client.waiting_stream_with(Some([TellStatusField::Status]), pages(3, 1));
+    let mut stream = Box::pin(stream);
+    let mut read = take(&mut stream, 3).await;
+
+    let add = AddUri::uris(vec!["http://example.com/front.iso"]).position(Some(0));
+    client.call(add).await.unwrap();
+    // the rest of the queue shifted back by one, pages overlap what was read
+    read.extend(take(&mut stream, usize::MAX).await);
+    assert_eq!(read, waiting);
+}
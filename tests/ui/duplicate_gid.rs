@@ -0,0 +1,13 @@
+use aria2_rs_yet::call::Aria2Call;
+use aria2_rs_yet::Gid;
+
+#[derive(Aria2Call)]
+#[aria2(method = "aria2.test", response = String)]
+struct DuplicateGid {
+    #[aria2(gid)]
+    a: Gid,
+    #[aria2(gid)]
+    b: Gid,
+}
+
+fn main() {}
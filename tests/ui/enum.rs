@@ -0,0 +1,9 @@
+use aria2_rs_yet::call::Aria2Call;
+
+#[derive(Aria2Call)]
+#[aria2(method = "aria2.test", response = String)]
+enum NotAStruct {
+    A,
+}
+
+fn main() {}
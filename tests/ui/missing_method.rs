@@ -0,0 +1,7 @@
+use aria2_rs_yet::call::Aria2Call;
+
+#[derive(Aria2Call)]
+#[aria2(response = String)]
+struct MissingMethod;
+
+fn main() {}
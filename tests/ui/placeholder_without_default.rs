@@ -0,0 +1,13 @@
+use aria2_rs_yet::call::Aria2Call;
+
+#[derive(serde::Serialize)]
+struct NoDefault;
+
+#[derive(Aria2Call)]
+#[aria2(method = "aria2.test", response = String)]
+struct Placeholder {
+    a: Option<NoDefault>,
+    b: i32,
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use aria2_rs_yet::call::Aria2Call;
+
+#[derive(Aria2Call)]
+#[aria2(method = "aria2.test", response = String, retry)]
+struct UnknownContainer;
+
+#[derive(Aria2Call)]
+#[aria2(method = "aria2.test", response = String)]
+struct UnknownField {
+    #[aria2(rename)]
+    gid: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use aria2_rs_yet::projection::Projection;
+
+#[derive(Projection)]
+struct Progress {
+    total_length: u64,
+    downloaded: u64,
+}
+
+fn main() {}